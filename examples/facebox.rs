@@ -4,6 +4,7 @@ extern crate serde_json;
 use std::thread;
 use std::time::Duration;
 use machinebox::facebox::Facebox;
+use machinebox::ImageSource;
 
 fn main() {
     if let Err(e) = face_sample() {
@@ -14,16 +15,16 @@ fn main() {
 fn face_sample() -> Result<(), machinebox::Error> {
     let facebox = Facebox::new("http://localhost:8080");
 
-    let _res = facebox.teach_url("https://machinebox.io/samples/faces/john.jpg",
-        "john.jpg", "John Lennon")?;
+    let john = ImageSource::url("https://machinebox.io/samples/faces/john.jpg");
+    facebox.teach(john, "john.jpg", "John Lennon")?;
     println!("Taught facebox by URL, waiting...");
 
     thread::sleep(Duration::from_secs(4)); // Allow the box to update internal data
 
-    let check_response = facebox.check_url("https://machinebox.io/samples/faces/thebeatles.jpg")?;
+    let check_response = facebox.check(ImageSource::url("https://machinebox.io/samples/faces/thebeatles.jpg"))?;
     println!("Performed face scan by URL.");
 
-    let matched: Vec<_> = check_response.faces.iter().filter(|f| f.matched == true).collect();
+    let matched: Vec<_> = check_response.faces.iter().filter(|f| f.matched).collect();
 
     println!("{:#?}", matched);
 
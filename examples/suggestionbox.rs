@@ -36,7 +36,7 @@ fn suggestion_sample() -> Result<(), machinebox::Error> {
         let predict = sb.predict("movie_demo", &request)?;
         let diehard = predict.choices.iter().find(|c| c.id == "diehard");
         match diehard {
-            Some(ref prediction) => {
+            Some(prediction) => {
                 sb.reward("movie_demo", &prediction.reward_id, 1.0)?;
             }
             None => {
@@ -55,7 +55,7 @@ fn suggestion_sample() -> Result<(), machinebox::Error> {
     let mut file = File::create("examples/movie_demo.suggestionbox")?;
     sb.download_state("movie_demo", &mut file)?;
 
-    let _delres = sb.delete_model("movie_demo")?;
+    sb.delete_model("movie_demo")?;
 
     Ok(())
 }
@@ -4,6 +4,7 @@ extern crate serde_json;
 use std::thread;
 use std::time::Duration;
 use machinebox::tagbox::Tagbox;
+use machinebox::ImageSource;
 
 fn main() {
     if let Err(e) = tag_sample() {
@@ -15,28 +16,28 @@ fn tag_sample() -> Result<(), machinebox::Error> {
     let tagbox = Tagbox::new("http://localhost:8080");
 
     println!("Analyzing towerbridge.jpg...");
-    let tower = tagbox.check_url("https://machinebox.io/samples/images/towerbridge.jpg")?;
+    let tower = tagbox.check(ImageSource::url("https://machinebox.io/samples/images/towerbridge.jpg"))?;
 
     println!("tower tags: \n{:#?}", tower);
 
     println!("teaching about monkeys...");
-    let _ = tagbox.teach_url(
-        "https://machinebox.io/samples/images/monkey.jpg",
+    tagbox.teach(
+        ImageSource::url("https://machinebox.io/samples/images/monkey.jpg"),
         "monkeys",
-        Some("monkey.jpg".to_owned()),
+        Some("monkey.jpg"),
     )?;
     println!("Taught about monkeys, waiting a few seconds for index to refresh...");
 
     thread::sleep(Duration::from_secs(5));
-    let monkeycheck = tagbox.check_url("https://machinebox.io/samples/images/monkey.jpg")?;
+    let monkeycheck = tagbox.check(ImageSource::url("https://machinebox.io/samples/images/monkey.jpg"))?;
     println!("taught monkey result - {:#?}.", monkeycheck);
 
-    let _rename = tagbox.rename_custom_tag("monkey.jpg", "floobers")?;
+    tagbox.rename_custom_tag("monkey.jpg", "floobers")?;
 
-    let monkeycheck2 = tagbox.check_url("https://machinebox.io/samples/images/monkey.jpg")?;
+    let monkeycheck2 = tagbox.check(ImageSource::url("https://machinebox.io/samples/images/monkey.jpg"))?;
     println!("{:#?}", monkeycheck2.custom_tags);
 
     // Remove monkeys tag
-    let _ = tagbox.remove_custom_tag("monkey.jpg")?;
+    tagbox.remove_custom_tag("monkey.jpg")?;
     Ok(())
 }
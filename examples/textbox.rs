@@ -1,6 +1,6 @@
 extern crate machinebox;
 
-use machinebox::textbox::Textbox;
+use machinebox::textbox::{EntityType, Textbox};
 use machinebox::BoxClient;
 
 fn main() {
@@ -27,7 +27,7 @@ fn perform_analysis() -> Result<(), machinebox::Error> {
     let money = analysis.sentences[0]
         .entities
         .iter()
-        .find(|e| e.entity_type == "money");
+        .find(|e| e.entity_type == EntityType::Money);
     match money {
         Some(val) => println!("You specified {}", val.text),
         None => println!("You didn't indicate money"),
@@ -0,0 +1,219 @@
+use reqwest;
+use std::fmt;
+use std::time::Duration;
+
+use super::Result;
+use retry::RetryPolicy;
+
+/// A connection to a single machine box.
+///
+/// The connection owns the HTTP client used to talk to the box. The underlying client
+/// maintains a connection pool, so every request made through the same `Connection` (or
+/// any of its clones) reuses established connections rather than paying connection setup
+/// on each call. Cloning a connection is cheap and shares the same pool.
+#[derive(Clone, Debug)]
+pub struct Connection {
+    url: String,
+    client: reqwest::Client,
+    connect_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    credentials: Option<Credentials>,
+    retry_policy: RetryPolicy,
+}
+
+/// The username and password used to authenticate with a box that was started with
+/// `MB_BASICAUTH`
+#[derive(Clone)]
+struct Credentials {
+    username: String,
+    password: String,
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+impl Connection {
+    /// Creates a new connection to the box at the supplied URL, using a default
+    /// HTTP client.
+    pub fn new(url: &str) -> Connection {
+        Connection::with_client(url, reqwest::Client::new())
+    }
+
+    /// Creates a new connection to the box at the supplied URL that will issue all
+    /// of its requests through the supplied, pre-configured HTTP client.
+    pub fn with_client(url: &str, client: reqwest::Client) -> Connection {
+        Connection {
+            url: url.to_owned(),
+            client,
+            connect_timeout: None,
+            timeout: None,
+            credentials: None,
+            retry_policy: RetryPolicy::never(),
+        }
+    }
+
+    /// Creates a builder for a connection to the box at the supplied URL
+    pub fn builder(url: &str) -> ConnectionBuilder {
+        ConnectionBuilder::new(url)
+    }
+
+    /// Indicates the URL of the box
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The HTTP client shared by all requests made through this connection
+    pub fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    /// The maximum amount of time connecting to the box may take
+    pub fn connect_timeout(&self) -> Option<Duration> {
+        self.connect_timeout
+    }
+
+    /// The total amount of time a single request, including reading its response, may take
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// The policy that determines how requests that fail for transient reasons are retried
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    /// Attaches this connection's credentials, if any, to an outgoing request
+    pub(crate) fn authorize(&self, request: &mut reqwest::RequestBuilder) {
+        if let Some(ref credentials) = self.credentials {
+            request.basic_auth(credentials.username.clone(), Some(credentials.password.clone()));
+        }
+    }
+}
+
+/// A builder that allows for fluent configuration of a connection
+///
+/// The `connect_timeout` bounds how long the client waits to establish a connection to the
+/// box. The underlying HTTP client can't time out connecting by itself, so when it is set,
+/// a TCP connection to the box is opened and closed again before each attempt at a request,
+/// with each address the box's host resolves to given `connect_timeout` to accept it. This
+/// costs an extra connection per request, and doesn't bound resolving the host name.
+///
+/// The `read_timeout` bounds how long the client waits for the box: both for the response
+/// to a request to arrive, and for each subsequent read of the response body. The
+/// overall `timeout` bounds a single attempt at a request,
+/// from sending it through to reading the last byte of the response. No single wait for
+/// the box may last longer than it, and no further read of the response is started once
+/// it has elapsed.
+///
+/// A request that runs out of time fails with an error for which `is_timeout()` is true.
+///
+/// Boxes protected with `MB_BASICAUTH` require credentials, supplied via `basic_auth`. These
+/// are sent with every request made through the connection. If the box rejects them, the
+/// request fails with an error for which `is_unauthorized()` is true.
+///
+/// By default, failed requests are not retried. Supply a `RetryPolicy` to retry requests
+/// that fail for transient reasons.
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use machinebox::Connection;
+/// use machinebox::facebox::Facebox;
+///
+/// let conn = Connection::builder("http://localhost:8080")
+///     .connect_timeout(Duration::from_secs(2))
+///     .read_timeout(Duration::from_secs(10))
+///     .timeout(Duration::from_secs(30))
+///     .basic_auth("username", "password")
+///     .finish()
+///     .unwrap();
+/// let facebox = Facebox::with_connection(conn);
+/// ```
+#[derive(Debug)]
+pub struct ConnectionBuilder {
+    url: String,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    credentials: Option<Credentials>,
+    retry_policy: RetryPolicy,
+}
+
+impl ConnectionBuilder {
+    /// Creates a new connection builder for the box at the supplied URL
+    pub fn new(url: &str) -> ConnectionBuilder {
+        ConnectionBuilder {
+            url: url.to_owned(),
+            connect_timeout: None,
+            read_timeout: None,
+            timeout: None,
+            credentials: None,
+            retry_policy: RetryPolicy::never(),
+        }
+    }
+
+    /// Sets the maximum amount of time to wait for a connection to the box to be
+    /// established
+    pub fn connect_timeout(mut self, timeout: Duration) -> ConnectionBuilder {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum amount of time to wait for the box to answer a request, or to
+    /// send more of its response
+    pub fn read_timeout(mut self, timeout: Duration) -> ConnectionBuilder {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum amount of time a request may take in total, from connecting
+    /// through to reading the last byte of the response
+    pub fn timeout(mut self, timeout: Duration) -> ConnectionBuilder {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the HTTP basic authentication credentials to send with every request
+    pub fn basic_auth(mut self, username: &str, password: &str) -> ConnectionBuilder {
+        self.credentials = Some(Credentials {
+            username: username.to_owned(),
+            password: password.to_owned(),
+        });
+        self
+    }
+
+    /// Sets the policy used to retry requests that fail for transient reasons
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> ConnectionBuilder {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Creates the connection from the builder
+    pub fn finish(self) -> Result<Connection> {
+        let mut builder = reqwest::Client::builder();
+        // The client's timeout applies to every wait for the box, so capping it at the
+        // overall timeout keeps any one wait from outlasting the request as a whole
+        let client_timeout = match (self.read_timeout, self.timeout) {
+            (Some(read), Some(total)) => Some(read.min(total)),
+            (read, total) => read.or(total),
+        };
+        if let Some(timeout) = client_timeout {
+            builder.timeout(timeout);
+        }
+        let client = builder.build()?;
+
+        Ok(Connection {
+            url: self.url,
+            client,
+            connect_timeout: self.connect_timeout,
+            timeout: self.timeout,
+            credentials: self.credentials,
+            retry_policy: self.retry_policy,
+        })
+    }
+}
@@ -0,0 +1,162 @@
+extern crate mockito;
+
+use self::mockito::{mock, Matcher, SERVER_URL};
+use super::Facebox;
+use std::env;
+use std::fs::{self, File};
+use std::io::{Cursor, Write};
+use std::process;
+use ImageSource;
+
+const CHECK_PAYLOAD: &str = r#"{
+    "success": true,
+    "facesCount": 1,
+    "faces": [
+        {
+            "rect": { "top": 10, "left": 20, "width": 100, "height": 120 },
+            "id": "john.jpg",
+            "name": "John Lennon",
+            "matched": true,
+            "confidence": 0.87
+        }
+    ]
+}"#;
+
+#[test]
+fn check_url() {
+    let fb = Facebox::new(SERVER_URL);
+    let mock = mock("POST", "/facebox/check")
+        .match_body(r#"{"url":"https://test.machinebox.io/john.jpg"}"#)
+        .with_body(CHECK_PAYLOAD)
+        .create();
+    {
+        let res = fb.check(ImageSource::url("https://test.machinebox.io/john.jpg")).unwrap();
+        assert_eq!(res.faces.len(), 1);
+        assert_eq!(res.faces[0].name, Some("John Lennon".to_owned()));
+        assert!(res.faces[0].matched);
+    }
+    mock.assert();
+}
+
+#[test]
+fn check_base64() {
+    let fb = Facebox::new(SERVER_URL);
+    let mock = mock("POST", "/facebox/check")
+        .match_body("base64=aW1hZ2U%3D")
+        .with_body(CHECK_PAYLOAD)
+        .create();
+    {
+        let res = fb.check(ImageSource::base64("aW1hZ2U="));
+        assert!(res.is_ok());
+    }
+    mock.assert();
+}
+
+#[test]
+fn check_bytes() {
+    let fb = Facebox::new(SERVER_URL);
+    let mock = mock("POST", "/facebox/check")
+        .match_header("content-type", Matcher::Regex("^multipart/form-data".to_owned()))
+        .match_body(Matcher::Regex("(?s)name=\"file\".*jpegbytes".to_owned()))
+        .with_body(CHECK_PAYLOAD)
+        .create();
+    {
+        let res = fb.check(ImageSource::bytes(&b"jpegbytes"[..]));
+        assert!(res.is_ok());
+    }
+    mock.assert();
+}
+
+#[test]
+fn similar_path() {
+    let path = env::temp_dir().join(format!("machinebox-facebox-similar-{}.jpg", process::id()));
+    File::create(&path).unwrap().write_all(b"pathbytes").unwrap();
+
+    let fb = Facebox::new(SERVER_URL);
+    let mock = mock("POST", "/facebox/similar")
+        .match_body(Matcher::Regex("(?s)name=\"file\".*pathbytes".to_owned()))
+        .with_body(r#"{"success": true, "similar": []}"#)
+        .create();
+    {
+        let res = fb.similar(ImageSource::path(&path));
+        assert!(res.is_ok());
+    }
+    mock.assert();
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn similar_reader() {
+    let fb = Facebox::new(SERVER_URL);
+    let mock = mock("POST", "/facebox/similar")
+        .match_body(Matcher::Regex("(?s)name=\"file\".*readerbytes".to_owned()))
+        .with_body(r#"{"success": true, "similar": []}"#)
+        .create();
+    {
+        let res = fb.similar(ImageSource::reader(Cursor::new(b"readerbytes".to_vec())));
+        assert!(res.is_ok());
+    }
+    mock.assert();
+}
+
+#[test]
+fn teach_bytes_with_runtime_id() {
+    let id = format!("person-{}", 42);
+    let name = String::from("John Lennon");
+
+    let fb = Facebox::new(SERVER_URL);
+    let mock = mock("POST", "/facebox/teach")
+        .match_body(Matcher::Regex(
+            "(?s)name=\"file\".*facebytes.*name=\"id\"\\s*person-42.*name=\"name\"\\s*John Lennon"
+                .to_owned(),
+        ))
+        .with_body(r#"{"success": true}"#)
+        .create();
+    {
+        let res = fb.teach(ImageSource::bytes(&b"facebytes"[..]), &id, &name);
+        assert!(res.is_ok());
+    }
+    mock.assert();
+}
+
+#[test]
+fn teach_base64() {
+    let fb = Facebox::new(SERVER_URL);
+    let mock = mock("POST", "/facebox/teach")
+        .match_body("base64=aW1hZ2U%3D&id=john.jpg&name=John+Lennon")
+        .with_body(r#"{"success": true}"#)
+        .create();
+    {
+        let res = fb.teach(ImageSource::base64("aW1hZ2U="), "john.jpg", "John Lennon");
+        assert!(res.is_ok());
+    }
+    mock.assert();
+}
+
+#[test]
+fn teach_url() {
+    let fb = Facebox::new(SERVER_URL);
+    let mock = mock("POST", "/facebox/teach")
+        .match_body("url=https%3A%2F%2Ftest.machinebox.io%2Fjohn.jpg&id=john.jpg&name=John+Lennon")
+        .with_body(r#"{"success": true}"#)
+        .create();
+    {
+        let image = ImageSource::url("https://test.machinebox.io/john.jpg");
+        let res = fb.teach(image, "john.jpg", "John Lennon");
+        assert!(res.is_ok());
+    }
+    mock.assert();
+}
+
+#[test]
+fn post_state_reader() {
+    let fb = Facebox::new(SERVER_URL);
+    let upload = mock("POST", "/facebox/state")
+        .match_body(Matcher::Regex("(?s)name=\"file\".*statebytes".to_owned()))
+        .with_body(r#"{"success": true}"#)
+        .create();
+    {
+        fb.post_state_reader(Cursor::new(b"statebytes".to_vec())).unwrap();
+    }
+    upload.assert();
+}
@@ -1,5 +1,5 @@
 use super::Result;
-use super::{Error, Kind};
+use utils::box_error;
 
 /// Represents a detected face in an image
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -57,18 +57,12 @@ pub struct SimilarResponse {
     pub similar: Vec<Similar>,
 }
 
-impl Into<Result<SimilarResponse>> for SimilarResponseFull {
-    fn into(self) -> Result<SimilarResponse> {
-        if self.success {
-            Ok(SimilarResponse { similar: self.similar })
+impl From<SimilarResponseFull> for Result<SimilarResponse> {
+    fn from(response: SimilarResponseFull) -> Result<SimilarResponse> {
+        if response.success {
+            Ok(SimilarResponse { similar: response.similar })
         } else {
-            let s = match self.error {
-                Some(s) => s,
-                None => "Request failed".to_owned(),
-            };
-            Err(Error {
-                kind: Kind::Machinebox(s),
-            })
+            Err(box_error(response.error))
         }
     }
 }
@@ -95,18 +89,12 @@ pub struct RenameRequest {
 }
 
 
-impl Into<Result<CheckResponse>> for CheckResponseFull {
-    fn into(self) -> Result<CheckResponse> {
-        if self.success {
-            Ok(CheckResponse { faces: self.faces })
+impl From<CheckResponseFull> for Result<CheckResponse> {
+    fn from(response: CheckResponseFull) -> Result<CheckResponse> {
+        if response.success {
+            Ok(CheckResponse { faces: response.faces })
         } else {
-            let s = match self.error {
-                Some(s) => s,
-                None => "Request failed".to_owned(),
-            };
-            Err(Error {
-                kind: Kind::Machinebox(s),
-            })
+            Err(box_error(response.error))
         }
     }
 }
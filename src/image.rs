@@ -0,0 +1,69 @@
+use std::fmt;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// An image to be submitted to a box.
+///
+/// Every operation that accepts an image accepts it in any of these forms. Images streamed
+/// from a reader can only be sent once, so requests carrying them are never retried. All
+/// other sources can be resent if the request fails for a transient reason.
+/// # Examples
+/// ```no_run
+/// use machinebox::ImageSource;
+/// use machinebox::tagbox::Tagbox;
+///
+/// let tb = Tagbox::new("http://localhost:8080");
+/// let tags = tb.check(ImageSource::url("https://machinebox.io/samples/images/monkey.jpg"));
+/// let local = tb.check(ImageSource::path("monkey.jpg"));
+/// ```
+pub enum ImageSource {
+    /// An image streamed from a reader
+    Reader(Box<dyn Read + Send>),
+    /// An image file on the local file system
+    Path(PathBuf),
+    /// The raw bytes of an encoded image, such as the contents of a JPEG file
+    Bytes(Vec<u8>),
+    /// A base64 encoded image
+    Base64(String),
+    /// The URL of an image that the box can download
+    Url(String),
+}
+
+impl ImageSource {
+    /// An image streamed from the supplied reader
+    pub fn reader<R: Read + Send + 'static>(reader: R) -> ImageSource {
+        ImageSource::Reader(Box::new(reader))
+    }
+
+    /// The image file at the supplied path
+    pub fn path<P: AsRef<Path>>(path: P) -> ImageSource {
+        ImageSource::Path(path.as_ref().to_path_buf())
+    }
+
+    /// The image contained in the supplied bytes
+    pub fn bytes<B: Into<Vec<u8>>>(bytes: B) -> ImageSource {
+        ImageSource::Bytes(bytes.into())
+    }
+
+    /// The image contained in the supplied base64 encoded string
+    pub fn base64(data: &str) -> ImageSource {
+        ImageSource::Base64(data.to_owned())
+    }
+
+    /// The image at the supplied URL
+    pub fn url(url: &str) -> ImageSource {
+        ImageSource::Url(url.to_owned())
+    }
+}
+
+impl fmt::Debug for ImageSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ImageSource::Reader(_) => f.write_str("Reader(..)"),
+            ImageSource::Path(ref path) => f.debug_tuple("Path").field(path).finish(),
+            ImageSource::Bytes(ref bytes) => write!(f, "Bytes({} bytes)", bytes.len()),
+            ImageSource::Base64(ref data) => write!(f, "Base64({} chars)", data.len()),
+            ImageSource::Url(ref url) => f.debug_tuple("Url").field(url).finish(),
+        }
+    }
+}
@@ -5,34 +5,111 @@
 //! more information on which boxes are available and their functionality, please check
 //! the [machinebox.io documentation](https://machinebox.io/docs/).
 
+extern crate base64;
+extern crate chrono;
 extern crate reqwest;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+extern crate sha2;
+#[cfg(feature = "async")]
+extern crate futures_channel;
 
 use reqwest::StatusCode;
 use std::fmt;
 
 /// Represents an error communicating with a machinebox
+///
+/// The `kind` of the error describes what went wrong. For the most common questions,
+/// such as whether a request is worth repeating, there are helper methods.
 #[derive(Debug)]
 pub struct Error {
     kind: Kind,
 }
 
 impl Error {
+    /// Creates a new error indicating that a box reported a failure with the given message
     pub fn new(msg: &str) -> Error {
         Error {
-            kind: Kind::Machinebox(msg.to_owned()),
+            kind: Kind::Box(msg.to_owned()),
         }
     }
+
+    /// The kind of error that occurred
+    pub fn kind(&self) -> &Kind {
+        &self.kind
+    }
+
+    /// The HTTP status code with which the box answered, if the error was caused by an
+    /// unexpected status
+    pub fn status(&self) -> Option<u16> {
+        match self.kind {
+            Kind::Http { status, .. } => Some(status),
+            Kind::Unauthorized => Some(401),
+            _ => None,
+        }
+    }
+
+    /// The raw body of the box's response, if the error was caused by an unexpected status
+    /// or by a response that could not be understood
+    pub fn body(&self) -> Option<&str> {
+        match self.kind {
+            Kind::Http { ref body, .. } | Kind::Deserialization { ref body, .. } => Some(body),
+            _ => None,
+        }
+    }
+
+    /// Indicates whether the box reported that the requested item doesn't exist (HTTP 404)
+    pub fn is_not_found(&self) -> bool {
+        self.status() == Some(404)
+    }
+
+    /// Indicates whether the request, or the wait for a video to be processed, was
+    /// abandoned because it ran out of time
+    pub fn is_timeout(&self) -> bool {
+        matches!(self.kind, Kind::Timeout | Kind::VideoTimeout { .. })
+    }
+
+    /// Indicates whether the box rejected the request's credentials (HTTP 401)
+    pub fn is_unauthorized(&self) -> bool {
+        matches!(self.kind, Kind::Unauthorized)
+    }
+
+    /// Indicates whether the failure is likely to be transient, such that repeating the
+    /// request later may succeed. This is the case for transport failures, timeouts, and
+    /// boxes that are overloaded or still starting up.
+    pub fn is_retryable(&self) -> bool {
+        match self.kind {
+            Kind::Transport(_) | Kind::Timeout => true,
+            Kind::Http { status, .. } => retry::TRANSIENT_STATUSES.contains(&status),
+            _ => false,
+        }
+    }
+}
+
+fn is_io_timeout(e: &std::io::Error) -> bool {
+    matches!(e.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock)
+}
+
+impl From<Kind> for Error {
+    fn from(kind: Kind) -> Self {
+        Error { kind }
+    }
 }
 
 impl From<reqwest::Error> for Error {
     fn from(source: reqwest::Error) -> Self {
-        Error {
-            kind: Kind::Reqwest(source),
-        }
+        let timed_out = source
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<std::io::Error>())
+            .is_some_and(is_io_timeout);
+        let kind = if timed_out {
+            Kind::Timeout
+        } else {
+            Kind::Transport(source)
+        };
+        Error { kind }
     }
 }
 
@@ -46,46 +123,234 @@ impl From<serde_json::Error> for Error {
 
 impl From<std::io::Error> for Error {
     fn from(source: std::io::Error) -> Self {
-        Error {
-            kind: Kind::Io(source),
-        }
+        let kind = if is_io_timeout(&source) {
+            Kind::Timeout
+        } else {
+            Kind::Io(source)
+        };
+        Error { kind }
     }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.kind {
-            Kind::Machinebox(ref s) => fmt::Display::fmt(s, f),
+            Kind::Http { status, ref body } => write!(f, "HTTP {}: {}", status, body),
+            Kind::Box(ref s) => fmt::Display::fmt(s, f),
+            Kind::Transport(ref e) => fmt::Display::fmt(e, f),
+            Kind::Timeout => f.write_str("The request to the box timed out"),
+            Kind::Unauthorized => f.write_str("The box rejected the supplied credentials"),
+            Kind::Deserialization { ref source, .. } => {
+                write!(f, "Failed to understand the response from the box: {}", source)
+            }
             Kind::Serialization(ref e) => fmt::Display::fmt(e, f),
-            Kind::Reqwest(ref e) => fmt::Display::fmt(e, f),
             Kind::Io(ref e) => fmt::Display::fmt(e, f),
+            Kind::VideoFailed { ref id, error: Some(ref error) } => {
+                write!(f, "Processing of video {} failed: {}", id, error)
+            }
+            Kind::VideoFailed { ref id, error: None } => {
+                write!(f, "Processing of video {} failed", id)
+            }
+            Kind::VideoExpired { ref id } => {
+                write!(f, "The results of video {} have expired or were removed", id)
+            }
+            Kind::VideoTimeout { ref id } => {
+                write!(f, "Gave up waiting for video {} to be processed", id)
+            }
+            Kind::InvalidOptions(ref reason) => write!(f, "Invalid options: {}", reason),
+            Kind::Base64(ref e) => write!(f, "Malformed base64 data: {}", e),
+            Kind::InvalidModel(ref errors) => {
+                f.write_str("Invalid model: ")?;
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str("; ")?;
+                    }
+                    fmt::Display::fmt(error, f)?;
+                }
+                Ok(())
+            }
+            Kind::ChecksumMismatch { ref path, ref expected, ref actual } => {
+                write!(f, "Snapshot {} has checksum {}, expected {}", path.display(), actual,
+                       expected)
+            }
+            Kind::SnapshotMismatch { ref name, ref model_id, ref target_name,
+                                     ref target_model_id } => {
+                write!(f, "A snapshot of {} cannot be restored to {}",
+                       describe_state(name, model_id), describe_state(target_name, target_model_id))
+            }
+            Kind::InvalidSnapshotFile { ref manifest, ref file } => {
+                write!(f, "Snapshot manifest {} names {:?}, which is not a file in its directory",
+                       manifest.display(), file)
+            }
+            Kind::RewardExpired { ref model_id, ref reward_id } => {
+                write!(f, "Reward {} for model {} has expired", reward_id, model_id)
+            }
+            Kind::RewardModelMismatch { ref model_id, ref reward_id, ref expected } => {
+                write!(f, "Reward {} belongs to model {}, not model {}", reward_id, expected,
+                       model_id)
+            }
+            Kind::AlreadyRewarded { ref model_id, ref reward_id } => {
+                write!(f, "The prediction that handed out reward {} for model {} has already \
+                           been rewarded", reward_id, model_id)
+            }
+            Kind::WorkerPanicked(ref message) => {
+                write!(f, "The worker thread performing the request panicked: {}", message)
+            }
         }
     }
 }
 
-impl std::error::Error for Error {
-    fn description(&self) -> &str {
-        "A machinebox client error occurred"
+/// Describes the box, and the model for suggestionbox, that a state belongs to
+fn describe_state(name: &str, model_id: &Option<String>) -> String {
+    match *model_id {
+        Some(ref model_id) => format!("{} model {}", name, model_id),
+        None => name.to_owned(),
     }
+}
 
-    fn cause(&self) -> Option<&std::error::Error> {
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self.kind {
-            Kind::Machinebox(_) => None,
+            Kind::Transport(ref e) => Some(e),
+            Kind::Deserialization { ref source, .. } => Some(source),
             Kind::Serialization(ref e) => Some(e),
-            Kind::Reqwest(ref e) => Some(e),
             Kind::Io(ref e) => Some(e),
+            Kind::Base64(ref e) => Some(e),
+            _ => None,
         }
     }
 }
 
-/// Kind indicates the type of error that occurred. External consumers of this library
-/// shouldn't have to deal with these values
+/// Kind indicates the type of error that occurred. New kinds may be added in the future,
+/// so matches against this enum must include a wildcard arm.
+///
+/// The kinds up to and including `Io` can come from any box. The rest are produced only
+/// by the APIs named in their descriptions.
 #[derive(Debug)]
-enum Kind {
-    Reqwest(::reqwest::Error),
+#[non_exhaustive]
+pub enum Kind {
+    /// The box answered with an unexpected HTTP status code
+    Http {
+        /// The HTTP status code
+        status: u16,
+        /// The raw body of the response
+        body: String,
+    },
+    /// The box processed the request, but reported that it failed. This contains the
+    /// error message supplied by the box.
+    Box(String),
+    /// The request could not be delivered to the box, or its response could not be received
+    Transport(::reqwest::Error),
+    /// The request ran out of time
+    Timeout,
+    /// The box rejected the supplied credentials
+    Unauthorized,
+    /// The response from the box could not be understood
+    Deserialization {
+        /// The underlying parse error
+        source: ::serde_json::Error,
+        /// The raw body of the response
+        body: String,
+    },
+    /// Data supplied locally, such as a model file, could not be (de)serialized
     Serialization(::serde_json::Error),
-    Machinebox(String),
+    /// An I/O error occurred, such as while reading an image or writing a state file
     Io(::std::io::Error),
+    /// Videobox reported that it failed to process a video. Produced by `Videobox::wait`
+    /// and the functions built on it, `check_and_wait` and `JobRegistry::resume`.
+    VideoFailed {
+        /// The ID of the video
+        id: String,
+        /// The reason given by the box, if any
+        error: Option<String>,
+    },
+    /// The results of a video expired, or were removed, before they could be retrieved.
+    /// Produced by `Videobox::wait`, `check_and_wait` and `JobRegistry::resume`.
+    VideoExpired {
+        /// The ID of the video
+        id: String,
+    },
+    /// A video was not processed before the deadline for waiting on it passed. Processing
+    /// continues on the box, so the video can be waited on again. Produced by
+    /// `Videobox::wait`, `check_and_wait` and `JobRegistry::resume`.
+    VideoTimeout {
+        /// The ID of the video
+        id: String,
+    },
+    /// Options supplied to a builder were out of range or contradicted each other. This
+    /// contains a description of the problem. Produced by the videobox
+    /// `CheckOptionsBuilder::finish`.
+    InvalidOptions(String),
+    /// Data the box supplied base64 encoded could not be decoded. Produced by the videobox
+    /// `Video::last_frame`, `write_last_frame` and `save_last_frame`.
+    Base64(::base64::DecodeError),
+    /// A suggestionbox model or choice failed validation, and was not sent to the box.
+    /// This contains every problem that was found. Produced by `ModelBuilder::finish` and
+    /// by the `Suggestionbox` and `Simulator` functions that create or change models.
+    InvalidModel(Vec<::suggestionbox::ValidationError>),
+    /// A state snapshot no longer matches the checksum recorded when it was taken, and
+    /// was not uploaded. Produced by `SnapshotManager::verify` and `restore`.
+    ChecksumMismatch {
+        /// The path of the state file
+        path: ::std::path::PathBuf,
+        /// The SHA-256 checksum recorded in the manifest
+        expected: String,
+        /// The SHA-256 checksum of the file as it is now
+        actual: String,
+    },
+    /// A state snapshot was taken from a different box, or a different suggestionbox
+    /// model, than the one it was to be restored to. Produced by `SnapshotManager::restore`
+    /// and by `ModelState::restore_state`.
+    SnapshotMismatch {
+        /// The name of the box the snapshot was taken from
+        name: String,
+        /// The model the snapshot was taken from, if any
+        model_id: Option<String>,
+        /// The name of the box the snapshot was to be restored to
+        target_name: String,
+        /// The model the snapshot was to be restored to, if any
+        target_model_id: Option<String>,
+    },
+    /// A snapshot manifest names a state file that isn't a plain file name within the
+    /// snapshot directory, such as one containing a path separator or `..`. Nothing is read
+    /// or removed outside the directory. Produced by the `SnapshotManager` functions that
+    /// read the manifest.
+    InvalidSnapshotFile {
+        /// The path of the manifest
+        manifest: ::std::path::PathBuf,
+        /// The file name recorded in the manifest
+        file: String,
+    },
+    /// A suggestionbox reward was not posted because the prediction it belongs to has
+    /// passed its model's reward expiration. Produced by `RewardLedger::reward`.
+    RewardExpired {
+        /// The ID of the model
+        model_id: String,
+        /// The reward ID
+        reward_id: String,
+    },
+    /// A suggestionbox reward was not posted because its reward ID was handed out by a
+    /// different model than the one it was posted to. Produced by `RewardLedger::reward`.
+    RewardModelMismatch {
+        /// The ID of the model the reward was posted to
+        model_id: String,
+        /// The reward ID
+        reward_id: String,
+        /// The ID of the model whose prediction handed out the reward ID
+        expected: String,
+    },
+    /// A suggestionbox reward was not posted because the prediction it belongs to has
+    /// already been rewarded. Produced by `RewardLedger::reward`.
+    AlreadyRewarded {
+        /// The ID of the model
+        model_id: String,
+        /// The reward ID
+        reward_id: String,
+    },
+    /// The worker thread performing a request panicked, and the request's outcome is
+    /// unknown. This contains the panic message, if there was one. Produced by the futures
+    /// returned from the `nonblocking` clients.
+    WorkerPanicked(String),
 }
 
 type Result<T> = std::result::Result<T, Error>;
@@ -132,58 +397,41 @@ pub trait BoxClient {
     /// Provides information about the box
     fn info(&self) -> Result<BoxInfo> {
         let url = format!("{}/info", self.url());
-        match reqwest::get(&url) {
-            Ok(mut result) => {
-                let raw = result.text()?;
-                let bi: BoxInfo = serde_json::from_str(&raw)?;
-                Ok(bi)
-            }
-            Err(e) => Err(Error {
-                kind: Kind::Reqwest(e),
-            }),
-        }
+        let raw = utils::get(self.connection(), &url)?.text()?;
+        let bi: BoxInfo = utils::parse(&raw)?;
+        Ok(bi)
     }
 
     /// Checks the health of the box
     fn health(&self) -> Result<Health> {
         let url = format!("{}/healthz", self.url());
-        match reqwest::get(&url) {
-            Ok(mut result) => {
-                let raw = result.text()?;
-                let health: Health = serde_json::from_str(&raw)?;
-                Ok(health)
-            }
-            Err(e) => Err(Error {
-                kind: Kind::Reqwest(e),
-            }),
-        }
+        let raw = utils::get(self.connection(), &url)?.text()?;
+        let health: Health = utils::parse(&raw)?;
+        Ok(health)
     }
 
     /// Determines whether the box is live
     fn is_live(&self) -> Result<bool> {
         let url = format!("{}/liveness", self.url());
-        match reqwest::get(&url) {
-            Ok(response) => Ok(response.status() == StatusCode::Ok),
-            Err(e) => Err(Error {
-                kind: Kind::Reqwest(e),
-            }),
-        }
+        let response = utils::get(self.connection(), &url)?;
+        Ok(response.status() == StatusCode::Ok)
     }
 
     /// Determines if the box is ready. Some boxes may take a while to start up, so you can
     /// use this function to check if it is acceptable to start using the box-specific functionality
     fn is_ready(&self) -> Result<bool> {
         let url = format!("{}/readyz", self.url());
-        match reqwest::get(&url) {
-            Ok(response) => Ok(response.status() == StatusCode::Ok),
-            Err(e) => Err(Error {
-                kind: Kind::Reqwest(e),
-            }),
-        }
+        let response = utils::get(self.connection(), &url)?;
+        Ok(response.status() == StatusCode::Ok)
     }
 
     /// Indicates the URL of the box
-    fn url(&self) -> &str;
+    fn url(&self) -> &str {
+        self.connection().url()
+    }
+
+    /// The connection through which all requests to the box are made
+    fn connection(&self) -> &Connection;
 }
 
 pub mod textbox;
@@ -191,21 +439,47 @@ pub mod suggestionbox;
 pub mod tagbox;
 pub mod facebox;
 pub mod videobox;
+pub mod snapshot;
+#[cfg(feature = "async")]
+pub mod nonblocking;
+
+pub use connection::{Connection, ConnectionBuilder};
+pub use image::ImageSource;
+pub use retry::RetryPolicy;
 
+mod connection;
+mod image;
+mod retry;
 mod utils;
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     extern crate mockito;
 
     use self::mockito::{mock, reset, SERVER_URL};
-    use BoxClient;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use std::error::Error as StdError;
+    use {BoxClient, Connection, Kind, RetryPolicy};
+
+    struct TestClient {
+        conn: Connection,
+    }
 
-    struct TestClient;
+    impl TestClient {
+        fn new() -> TestClient {
+            TestClient {
+                conn: Connection::new(SERVER_URL),
+            }
+        }
+    }
 
     impl BoxClient for TestClient {
-        fn url(&self) -> &str {
-            &SERVER_URL
+        fn connection(&self) -> &Connection {
+            &self.conn
         }
     }
 
@@ -225,7 +499,7 @@ mod tests {
             )
             .create();
         {
-            let t = TestClient {};
+            let t = TestClient::new();
             let info = t.info().unwrap();
             assert_eq!(info.success, true);
             assert_eq!(info.name, "tagbox");
@@ -237,6 +511,22 @@ mod tests {
         mock.assert();
     }
 
+    #[test]
+    fn malformed_response_keeps_body() {
+        let mock = mock("GET", "/info").with_body("<html>gateway</html>").create();
+        {
+            let t = TestClient::new();
+            let err = t.info().unwrap_err();
+            match *err.kind() {
+                Kind::Deserialization { .. } => {}
+                ref other => panic!("unexpected error kind: {:?}", other),
+            }
+            assert_eq!(err.body(), Some("<html>gateway</html>"));
+            assert!(err.source().is_some());
+        }
+        mock.assert();
+    }
+
     #[test]
     fn health_parses_no_error() {
         let mock = mock("GET", "/healthz")
@@ -254,7 +544,7 @@ mod tests {
             )
             .create();
         {
-            let t = TestClient {};
+            let t = TestClient::new();
             let health = t.health().unwrap();
             assert_eq!(health.success, true);
             assert_eq!(health.hostname, "83b1a33ef322");
@@ -284,7 +574,7 @@ mod tests {
             )
             .create();
         {
-            let t = TestClient {};
+            let t = TestClient::new();
             let health = t.health().unwrap();
             assert_eq!(health.success, false);
             assert_eq!(health.hostname, "83b1a33ef322");
@@ -299,7 +589,7 @@ mod tests {
     fn islive_checks_statuscode() {
         let mock = mock("GET", "/liveness").with_status(200).create();
         {
-            let t = TestClient {};
+            let t = TestClient::new();
             let live = t.is_live().unwrap();
             assert_eq!(live, true);
         }
@@ -310,7 +600,7 @@ mod tests {
     fn ready_checks_statuscode() {
         let mock = mock("GET", "/readyz").with_status(200).create();
         {
-            let t = TestClient {};
+            let t = TestClient::new();
             let ready = t.is_ready().unwrap();
             assert_eq!(ready, true);
         }
@@ -321,10 +611,166 @@ mod tests {
     fn ready_checks_statuscode_fail() {
         let mock = mock("GET", "/readyz").with_status(503).create();
         {
-            let t = TestClient {};
+            let t = TestClient::new();
             let ready = t.is_ready().unwrap();
             assert_eq!(ready, false);
         }
         mock.assert();
     }
+
+    #[test]
+    fn clients_share_a_connection() {
+        let mock = mock("GET", "/liveness").with_status(200).expect(2).create();
+        {
+            let conn = Connection::new(SERVER_URL);
+            let first = TestClient { conn: conn.clone() };
+            let second = TestClient { conn };
+            assert_eq!(first.url(), second.url());
+            assert_eq!(first.is_live().unwrap(), true);
+            assert_eq!(second.is_live().unwrap(), true);
+        }
+        mock.assert();
+    }
+
+    #[test]
+    fn timeout_is_reported() {
+        // Accepts connections but never answers them
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let conn = Connection::builder(&url)
+            .timeout(Duration::from_millis(250))
+            .finish()
+            .unwrap();
+        let t = TestClient { conn };
+        let res = t.info();
+        assert!(res.is_err());
+        assert!(res.unwrap_err().is_timeout());
+    }
+
+    #[test]
+    fn connect_timeout_is_reported() {
+        // A listener that never accepts stops completing connections once its backlog is
+        // full, so further attempts to connect hang
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut backlog = Vec::new();
+        while let Ok(stream) = TcpStream::connect_timeout(&addr, Duration::from_millis(100)) {
+            backlog.push(stream);
+            assert!(backlog.len() < 4096, "the listener's backlog never filled up");
+        }
+        let conn = Connection::builder(&format!("http://{}", addr))
+            .connect_timeout(Duration::from_millis(100))
+            .read_timeout(Duration::from_secs(30))
+            .finish()
+            .unwrap();
+        let t = TestClient { conn };
+        let started = Instant::now();
+        match *t.info().unwrap_err().kind() {
+            Kind::Timeout => {}
+            ref other => panic!("unexpected error kind: {:?}", other),
+        }
+        // Well before the read timeout would have given up
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    /// Answers a single request with `body`, after waiting `delay`, writing the body in
+    /// chunks `pause` apart. Returns the URL to reach the server at.
+    fn serve_slowly(delay: Duration, body: &'static str, chunks: usize, pause: Duration)
+                    -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            thread::sleep(delay);
+            let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len());
+            let _ = stream.write_all(head.as_bytes());
+            let size = body.len().div_ceil(chunks);
+            for chunk in body.as_bytes().chunks(size) {
+                let _ = stream.write_all(chunk).and_then(|_| stream.flush());
+                thread::sleep(pause);
+            }
+        });
+        url
+    }
+
+    const INFO: &str = r#"{"success":true,"name":"facebox","version":1,"build":"abc",
+        "status":"ready","plan":"developer"}"#;
+
+    #[test]
+    fn slow_answers_within_read_timeout_succeed() {
+        let url = serve_slowly(Duration::from_millis(400), INFO, 1, Duration::from_millis(0));
+        let conn = Connection::builder(&url)
+            .read_timeout(Duration::from_secs(5))
+            .timeout(Duration::from_secs(10))
+            .finish()
+            .unwrap();
+        let t = TestClient { conn };
+        assert_eq!(t.info().unwrap().name, "facebox");
+    }
+
+    #[test]
+    fn overall_timeout_covers_reading_the_body() {
+        // Each chunk arrives well within the read timeout, but the body as a whole doesn't
+        let url = serve_slowly(Duration::from_millis(0), INFO, 10, Duration::from_millis(100));
+        let conn = Connection::builder(&url)
+            .read_timeout(Duration::from_secs(5))
+            .timeout(Duration::from_millis(300))
+            .finish()
+            .unwrap();
+        let t = TestClient { conn };
+        assert!(t.info().unwrap_err().is_timeout());
+    }
+
+    #[test]
+    fn credentials_are_sent_with_probes() {
+        let mock = mock("GET", "/readyz")
+            .match_header("authorization", "Basic dXNlcjpzZWNyZXQ=")
+            .with_status(200)
+            .create();
+        {
+            let conn = Connection::builder(SERVER_URL)
+                .basic_auth("user", "secret")
+                .finish()
+                .unwrap();
+            let t = TestClient { conn };
+            assert_eq!(t.is_ready().unwrap(), true);
+        }
+        mock.assert();
+    }
+
+    #[test]
+    fn unauthorized_is_reported() {
+        let mock = mock("GET", "/info").with_status(401).create();
+        {
+            let t = TestClient::new();
+            let res = t.info();
+            assert!(res.is_err());
+            assert!(res.unwrap_err().is_unauthorized());
+        }
+        mock.assert();
+    }
+
+    #[test]
+    fn transient_failures_are_retried() {
+        let mock = mock("GET", "/readyz").with_status(503).expect(3).create();
+        {
+            let conn = Connection::builder(SERVER_URL)
+                .retry_policy(RetryPolicy {
+                    max_attempts: 3,
+                    initial_backoff: Duration::from_millis(1),
+                    ..RetryPolicy::default()
+                })
+                .finish()
+                .unwrap();
+            let t = TestClient { conn };
+            assert_eq!(t.is_ready().unwrap(), false);
+        }
+        mock.assert();
+    }
 }
@@ -0,0 +1,105 @@
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+use super::{spawn, spawn_dedicated, AsyncBoxClient, BoxFuture};
+use facebox::{self, CheckResponse, SimilarResponse};
+use {Connection, ImageSource};
+
+/// The asynchronous client for the `facebox` machine box.
+#[derive(Clone)]
+pub struct Facebox {
+    inner: Arc<facebox::Facebox>,
+}
+
+impl Facebox {
+    /// Creates a new facebox client
+    pub fn new(url: &str) -> Facebox {
+        Facebox::with_connection(Connection::new(url))
+    }
+
+    /// Creates a new facebox client that makes all of its requests through the
+    /// supplied connection
+    pub fn with_connection(conn: Connection) -> Facebox {
+        Facebox {
+            inner: Arc::new(facebox::Facebox::with_connection(conn)),
+        }
+    }
+
+    /// Identifies the faces in the supplied image
+    pub fn check(&self, image: ImageSource) -> BoxFuture<CheckResponse> {
+        spawn(&self.inner, move |fb| fb.check(image))
+    }
+
+    /// Finds faces similar to those in the supplied image
+    pub fn similar(&self, image: ImageSource) -> BoxFuture<SimilarResponse> {
+        spawn(&self.inner, move |fb| fb.similar(image))
+    }
+
+    /// Finds faces similar to the taught face with the given ID
+    pub fn similar_id(&self, id: &str) -> BoxFuture<SimilarResponse> {
+        let id = id.to_owned();
+        spawn(&self.inner, move |fb| fb.similar_id(&id))
+    }
+
+    /// Downloads the state of the facebox into `buf`, handing the buffer back along
+    /// with the number of bytes written
+    pub fn download_state<W>(&self, mut buf: W) -> BoxFuture<(W, u64)>
+    where
+        W: Write + Send + 'static,
+    {
+        spawn_dedicated(&self.inner, move |fb| {
+            let bytecount = fb.download_state(&mut buf)?;
+            Ok((buf, bytecount))
+        })
+    }
+
+    /// Submits the state file indicated by the `source_path` parameter to the facebox
+    pub fn post_state(&self, source_path: &str) -> BoxFuture<()> {
+        let source_path = source_path.to_owned();
+        spawn_dedicated(&self.inner, move |fb| fb.post_state(&source_path))
+    }
+
+    /// Submits the state file read from `reader` to the facebox
+    pub fn post_state_reader<R: Read + Send + 'static>(&self, reader: R) -> BoxFuture<()> {
+        spawn_dedicated(&self.inner, move |fb| fb.post_state_reader(reader))
+    }
+
+    /// Submits a state URL to the facebox
+    pub fn post_state_url(&self, state_url: &str) -> BoxFuture<()> {
+        let state_url = state_url.to_owned();
+        spawn_dedicated(&self.inner, move |fb| fb.post_state_url(&state_url))
+    }
+
+    /// Teaches facebox the face in the supplied image, storing it under `id` with the
+    /// given `name`
+    pub fn teach(&self, image: ImageSource, id: &str, name: &str) -> BoxFuture<()> {
+        let (id, name) = (id.to_owned(), name.to_owned());
+        spawn(&self.inner, move |fb| fb.teach(image, &id, &name))
+    }
+
+    /// Removes a face from the facebox
+    pub fn remove(&self, id: &str) -> BoxFuture<()> {
+        let id = id.to_owned();
+        spawn(&self.inner, move |fb| fb.remove(&id))
+    }
+
+    /// Changes the name of a single face
+    pub fn rename(&self, id: &str, name: &str) -> BoxFuture<()> {
+        let (id, name) = (id.to_owned(), name.to_owned());
+        spawn(&self.inner, move |fb| fb.rename(&id, &name))
+    }
+
+    /// Renames every face that has the old name
+    pub fn rename_all(&self, old_name: &str, new_name: &str) -> BoxFuture<()> {
+        let (old_name, new_name) = (old_name.to_owned(), new_name.to_owned());
+        spawn(&self.inner, move |fb| fb.rename_all(&old_name, &new_name))
+    }
+}
+
+impl AsyncBoxClient for Facebox {
+    type Client = facebox::Facebox;
+
+    fn client(&self) -> &Arc<facebox::Facebox> {
+        &self.inner
+    }
+}
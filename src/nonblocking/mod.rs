@@ -0,0 +1,393 @@
+//! Asynchronous flavours of the box clients, available with the `async` feature
+//!
+//! Each client in this module mirrors its blocking counterpart method for method, and
+//! resolves to exactly the same request and response types. Requests are performed by
+//! the blocking clients on a pool of worker threads shared by all clients, so the
+//! returned futures never block the executor that polls them and can be awaited from any
+//! runtime, such as `tokio`. Unlike most futures, a request is started as soon as its
+//! future is created rather than when it is first polled. At most `workers()` requests
+//! are in progress on the pool at once; any further requests wait for a worker to become
+//! free. The size of the pool defaults to `DEFAULT_WORKERS` and can be changed with
+//! `set_workers` before the first request is made.
+//!
+//! Calls that may take arbitrarily long, such as uploading and downloading state or
+//! uploading a video, run on a separate set of transfer threads instead, so they can never
+//! starve the pool of workers for the short requests. Much like `spawn_blocking` in
+//! `tokio`, transfer threads are started on demand and exit once they have been idle for
+//! a while, but there are never more than `MAX_TRANSFERS` of them: further transfers
+//! queue until one finishes. A burst of transfers therefore doesn't start a thread per
+//! call, at the cost of a long transfer delaying the ones queued behind it. Waiting for a video to be processed polls
+//! its status with short requests on the pool and pauses on a shared timer thread in
+//! between, so a wait never holds on to a worker.
+//!
+//! # Examples
+//! ```no_run
+//! # extern crate futures_executor;
+//! # extern crate machinebox;
+//! use machinebox::ImageSource;
+//! use machinebox::nonblocking::Facebox;
+//!
+//! # fn main() {
+//! let facebox = Facebox::new("http://localhost:8080");
+//! let image = ImageSource::url("https://machinebox.io/samples/faces/thebeatles.jpg");
+//! let request = facebox.check(image);
+//! // in an async fn: `let faces = request.await?;`
+//! let faces = futures_executor::block_on(request).unwrap();
+//! # }
+//! ```
+use futures_channel::oneshot;
+use std::any::Any;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::mem;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Condvar, Mutex, OnceLock, PoisonError};
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{BoxClient, BoxInfo, Error, Health, Kind, Result};
+
+pub use self::facebox::Facebox;
+pub use self::suggestionbox::Suggestionbox;
+pub use self::tagbox::Tagbox;
+pub use self::textbox::Textbox;
+pub use self::videobox::{Videobox, VideoWait};
+
+mod facebox;
+mod suggestionbox;
+mod tagbox;
+mod textbox;
+mod videobox;
+
+/// The number of worker threads that perform requests for the asynchronous clients,
+/// unless a different number is chosen with `set_workers`
+pub const DEFAULT_WORKERS: usize = 8;
+
+/// The most transfers, such as state uploads and downloads or video uploads, that are
+/// in progress at once. Further transfers wait for one of them to finish.
+pub const MAX_TRANSFERS: usize = 4;
+
+/// How long an idle transfer thread waits for another transfer before it exits
+const TRANSFER_KEEP_ALIVE: Duration = Duration::from_secs(10);
+
+static WORKER_COUNT: OnceLock<usize> = OnceLock::new();
+
+/// Sets the number of worker threads shared by the asynchronous clients. The size of the
+/// pool is fixed once it has started, so this must be called before the first request is
+/// made; it fails with `Kind::InvalidOptions` if the pool has already started, or if
+/// `count` is zero.
+pub fn set_workers(count: usize) -> Result<()> {
+    if count == 0 {
+        return Err(Error::from(Kind::InvalidOptions(
+            "the worker pool needs at least one worker".to_owned(),
+        )));
+    }
+    WORKER_COUNT.set(count).map_err(|_| {
+        Error::from(Kind::InvalidOptions(
+            "the size of the worker pool can't be changed once it has started".to_owned(),
+        ))
+    })
+}
+
+/// The number of worker threads shared by the asynchronous clients. Querying it fixes the
+/// size of the pool, after which `set_workers` fails.
+pub fn workers() -> usize {
+    *WORKER_COUNT.get_or_init(|| DEFAULT_WORKERS)
+}
+
+/// A request to a box that is in progress on a worker thread. Resolves to the same
+/// result the blocking client would have returned, or to `Kind::WorkerPanicked` if the
+/// request panicked.
+///
+/// The request is sent as soon as the future is created, and runs to completion whether
+/// or not the future is polled; dropping the future only discards the result.
+#[must_use = "the request runs whether or not the future is polled, but its result is lost"]
+pub struct BoxFuture<T> {
+    receiver: oneshot::Receiver<Result<T>>,
+}
+
+impl<T> Future for BoxFuture<T> {
+    type Output = Result<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<T>> {
+        match Pin::new(&mut self.receiver).poll(cx) {
+            Poll::Ready(Ok(result)) => Poll::Ready(result),
+            Poll::Ready(Err(oneshot::Canceled)) => Poll::Ready(Err(Error::from(
+                Kind::WorkerPanicked("no worker thread is available".to_owned()),
+            ))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Hands `job` to the worker pool, starting the pool on first use. If no worker could be
+/// started the job is dropped, which the waiting `BoxFuture` reports as an error.
+fn execute(job: Job) {
+    static POOL: OnceLock<Sender<Job>> = OnceLock::new();
+    let pool = POOL.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for i in 0..workers() {
+            let receiver = Arc::clone(&receiver);
+            let _ = thread::Builder::new()
+                .name(format!("machinebox-worker-{}", i))
+                .spawn(move || loop {
+                    // The lock is released before the job runs, so other workers can take
+                    // the next one
+                    let next = match receiver.lock() {
+                        Ok(receiver) => receiver.recv(),
+                        Err(_) => return,
+                    };
+                    match next {
+                        Ok(job) => job(),
+                        Err(_) => return,
+                    }
+                });
+        }
+        sender
+    });
+    let _ = pool.send(job);
+}
+
+/// Extracts the message from the payload of a panic
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_owned()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::new()
+    }
+}
+
+/// Wraps `request` against the blocking `client` into a job, along with the future that
+/// resolves to its result
+fn job<C, T, F>(client: &Arc<C>, request: F) -> (Job, BoxFuture<T>)
+where
+    C: Send + Sync + 'static,
+    T: Send + 'static,
+    F: FnOnce(&C) -> Result<T> + Send + 'static,
+{
+    let (sender, receiver) = oneshot::channel();
+    let client = Arc::clone(client);
+    let job = Box::new(move || {
+        // A panicking request must not take its worker down with it
+        let result = panic::catch_unwind(AssertUnwindSafe(|| request(&client)))
+            .unwrap_or_else(|payload| {
+                Err(Error::from(Kind::WorkerPanicked(panic_message(&*payload))))
+            });
+        // The caller may have dropped the future, in which case nobody wants the result
+        let _ = sender.send(result);
+    });
+    (job, BoxFuture { receiver })
+}
+
+/// Runs `request` against the blocking `client` on the worker pool
+fn spawn<C, T, F>(client: &Arc<C>, request: F) -> BoxFuture<T>
+where
+    C: Send + Sync + 'static,
+    T: Send + 'static,
+    F: FnOnce(&C) -> Result<T> + Send + 'static,
+{
+    let (job, future) = job(client, request);
+    execute(job);
+    future
+}
+
+/// Runs `request` against the blocking `client` on a transfer thread, for calls that may
+/// hold on to a thread for much longer than an ordinary request
+fn spawn_dedicated<C, T, F>(client: &Arc<C>, request: F) -> BoxFuture<T>
+where
+    C: Send + Sync + 'static,
+    T: Send + 'static,
+    F: FnOnce(&C) -> Result<T> + Send + 'static,
+{
+    let (job, future) = job(client, request);
+    transfer(job);
+    future
+}
+
+/// The transfers waiting for a thread, along with the transfer threads that are running
+#[derive(Default)]
+struct Transfers {
+    state: Mutex<TransferState>,
+    queued: Condvar,
+}
+
+#[derive(Default)]
+struct TransferState {
+    queue: VecDeque<Job>,
+    threads: usize,
+    idle: usize,
+}
+
+/// Queues `job` for a transfer thread, starting one if none is idle and fewer than
+/// `MAX_TRANSFERS` are running. If no transfer thread is running and none could be
+/// started the job is dropped, which the waiting `BoxFuture` reports as an error.
+fn transfer(job: Job) {
+    static TRANSFERS: OnceLock<Arc<Transfers>> = OnceLock::new();
+    let transfers = TRANSFERS.get_or_init(Arc::default);
+
+    let mut state = transfers.state.lock().unwrap_or_else(PoisonError::into_inner);
+    state.queue.push_back(job);
+    if state.idle > 0 {
+        transfers.queued.notify_one();
+        return;
+    }
+    if state.threads < MAX_TRANSFERS {
+        let shared = Arc::clone(transfers);
+        let started = thread::Builder::new()
+            .name("machinebox-transfer".to_owned())
+            .spawn(move || run_transfers(&shared));
+        match started {
+            Ok(_) => state.threads += 1,
+            Err(_) if state.threads == 0 => state.queue.clear(),
+            Err(_) => {}
+        }
+    }
+}
+
+/// Runs queued transfers one after the other, exiting once none has arrived for
+/// `TRANSFER_KEEP_ALIVE`
+fn run_transfers(transfers: &Transfers) {
+    let mut state = transfers.state.lock().unwrap_or_else(PoisonError::into_inner);
+    loop {
+        if let Some(job) = state.queue.pop_front() {
+            // The lock is released while the transfer runs, so more can be queued
+            drop(state);
+            job();
+            state = transfers.state.lock().unwrap_or_else(PoisonError::into_inner);
+            continue;
+        }
+        state.idle += 1;
+        let (next, timeout) = transfers
+            .queued
+            .wait_timeout(state, TRANSFER_KEEP_ALIVE)
+            .unwrap_or_else(PoisonError::into_inner);
+        state = next;
+        state.idle -= 1;
+        if timeout.timed_out() && state.queue.is_empty() {
+            state.threads -= 1;
+            return;
+        }
+    }
+}
+
+/// Resolves once a pause has elapsed, without holding up a thread in the meantime. Fails
+/// with `Kind::WorkerPanicked` if the timer thread could not be started.
+struct Delay {
+    receiver: oneshot::Receiver<()>,
+}
+
+impl Future for Delay {
+    type Output = Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        match Pin::new(&mut self.receiver).poll(cx) {
+            Poll::Ready(Ok(())) => Poll::Ready(Ok(())),
+            Poll::Ready(Err(oneshot::Canceled)) => Poll::Ready(Err(Error::from(
+                Kind::WorkerPanicked("no timer thread is available".to_owned()),
+            ))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// The pauses that the timer thread is waiting on, each with the moment it ends
+#[derive(Default)]
+struct Timer {
+    pending: Mutex<Vec<(Instant, oneshot::Sender<()>)>>,
+    changed: Condvar,
+}
+
+/// Creates a `Delay` that resolves once `pause` has elapsed. All delays are tracked by a
+/// single timer thread, started on first use.
+fn delay(pause: Duration) -> Delay {
+    static TIMER: OnceLock<Option<Arc<Timer>>> = OnceLock::new();
+    let timer = TIMER.get_or_init(|| {
+        let timer = Arc::new(Timer::default());
+        let shared = Arc::clone(&timer);
+        thread::Builder::new()
+            .name("machinebox-timer".to_owned())
+            .spawn(move || run_timer(&shared))
+            .ok()
+            .map(|_| timer)
+    });
+
+    let (sender, receiver) = oneshot::channel();
+    // Without a timer thread the sender is dropped, which the `Delay` reports as an error
+    if let Some(ref timer) = *timer {
+        timer
+            .pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((Instant::now() + pause, sender));
+        timer.changed.notify_one();
+    }
+    Delay { receiver }
+}
+
+/// Resolves each pending delay as it ends, sleeping until the next one is due
+fn run_timer(timer: &Timer) {
+    let mut pending = timer.pending.lock().unwrap_or_else(PoisonError::into_inner);
+    loop {
+        let now = Instant::now();
+        let (due, waiting): (Vec<_>, Vec<_>) = mem::take(&mut *pending)
+            .into_iter()
+            .filter(|(_, sender)| !sender.is_canceled())
+            .partition(|&(end, _)| end <= now);
+        *pending = waiting;
+        for (_, sender) in due {
+            let _ = sender.send(());
+        }
+
+        pending = match pending.iter().map(|&(end, _)| end).min() {
+            Some(next) => {
+                timer
+                    .changed
+                    .wait_timeout(pending, next - now)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0
+            }
+            None => timer.changed.wait(pending).unwrap_or_else(PoisonError::into_inner),
+        };
+    }
+}
+
+/// The asynchronous counterpart of `BoxClient`, providing the probes that are
+/// available on all boxes
+pub trait AsyncBoxClient {
+    /// The blocking client that performs the requests
+    type Client: BoxClient + Send + Sync + 'static;
+
+    /// The blocking client shared with the worker threads
+    fn client(&self) -> &Arc<Self::Client>;
+
+    /// Queries information about the box
+    fn info(&self) -> BoxFuture<BoxInfo> {
+        spawn(self.client(), |c| c.info())
+    }
+
+    /// Queries the health of the box
+    fn health(&self) -> BoxFuture<Health> {
+        spawn(self.client(), |c| c.health())
+    }
+
+    /// Indicates whether the box is live
+    fn is_live(&self) -> BoxFuture<bool> {
+        spawn(self.client(), |c| c.is_live())
+    }
+
+    /// Indicates whether the box is ready
+    fn is_ready(&self) -> BoxFuture<bool> {
+        spawn(self.client(), |c| c.is_ready())
+    }
+}
+
+#[cfg(test)]
+mod tests;
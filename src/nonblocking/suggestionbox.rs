@@ -0,0 +1,110 @@
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+use super::{spawn, spawn_dedicated, AsyncBoxClient, BoxFuture};
+use suggestionbox::{self, Model, ModelStats, PredictionRequest, PredictionResponse};
+use Connection;
+
+/// The asynchronous client for the `suggestionbox` machine box.
+#[derive(Clone)]
+pub struct Suggestionbox {
+    inner: Arc<suggestionbox::Suggestionbox>,
+}
+
+impl Suggestionbox {
+    /// Creates a new suggestionbox client
+    pub fn new(url: &str) -> Suggestionbox {
+        Suggestionbox::with_connection(Connection::new(url))
+    }
+
+    /// Creates a new suggestionbox client that makes all of its requests through the
+    /// supplied connection
+    pub fn with_connection(conn: Connection) -> Suggestionbox {
+        Suggestionbox {
+            inner: Arc::new(suggestionbox::Suggestionbox::with_connection(conn)),
+        }
+    }
+
+    /// Creates a new model within the suggestion box
+    pub fn create_model(&self, model: &Model) -> BoxFuture<Model> {
+        let model = model.clone();
+        spawn(&self.inner, move |sb| sb.create_model(&model))
+    }
+
+    /// Deletes a model from the box
+    pub fn delete_model(&self, id: &str) -> BoxFuture<()> {
+        let id = id.to_owned();
+        spawn(&self.inner, move |sb| sb.delete_model(&id))
+    }
+
+    /// Retrieves a single model from the box
+    pub fn get_model(&self, id: &str) -> BoxFuture<Model> {
+        let id = id.to_owned();
+        spawn(&self.inner, move |sb| sb.get_model(&id))
+    }
+
+    /// Retrieves the list of all models in the box
+    pub fn list_models(&self) -> BoxFuture<Vec<Model>> {
+        spawn(&self.inner, |sb| sb.list_models())
+    }
+
+    /// Retrieves the statistics for a given model
+    pub fn get_model_stats(&self, id: &str) -> BoxFuture<ModelStats> {
+        let id = id.to_owned();
+        spawn(&self.inner, move |sb| sb.get_model_stats(&id))
+    }
+
+    /// Asks the suggestionbox to make a prediction based upon the supplied list of features
+    /// in the prediction request
+    pub fn predict(&self, model_id: &str, request: &PredictionRequest)
+                   -> BoxFuture<PredictionResponse> {
+        let (model_id, request) = (model_id.to_owned(), request.clone());
+        spawn(&self.inner, move |sb| sb.predict(&model_id, &request))
+    }
+
+    /// Tells the suggestionbox about a successful prediction
+    pub fn reward(&self, model_id: &str, reward_id: &str, weight: f64) -> BoxFuture<()> {
+        let (model_id, reward_id) = (model_id.to_owned(), reward_id.to_owned());
+        spawn(&self.inner, move |sb| sb.reward(&model_id, &reward_id, weight))
+    }
+
+    /// Downloads the state of the model into `buf`, handing the buffer back along
+    /// with the number of bytes written
+    pub fn download_state<W>(&self, model_id: &str, mut buf: W) -> BoxFuture<(W, u64)>
+    where
+        W: Write + Send + 'static,
+    {
+        let model_id = model_id.to_owned();
+        spawn_dedicated(&self.inner, move |sb| {
+            let bytecount = sb.download_state(&model_id, &mut buf)?;
+            Ok((buf, bytecount))
+        })
+    }
+
+    /// Submits the state file indicated by the `source_path` parameter to the suggestion box
+    /// and returns the model originally contained in the state file
+    pub fn post_state(&self, source_path: &str) -> BoxFuture<Model> {
+        let source_path = source_path.to_owned();
+        spawn_dedicated(&self.inner, move |sb| sb.post_state(&source_path))
+    }
+
+    /// Submits the state file read from `reader` to the suggestion box and returns the
+    /// model originally contained in it
+    pub fn post_state_reader<R: Read + Send + 'static>(&self, reader: R) -> BoxFuture<Model> {
+        spawn_dedicated(&self.inner, move |sb| sb.post_state_reader(reader))
+    }
+
+    /// Submits a state URL to the suggestion box and returns the model from the state file
+    pub fn post_state_url(&self, state_url: &str) -> BoxFuture<Model> {
+        let state_url = state_url.to_owned();
+        spawn_dedicated(&self.inner, move |sb| sb.post_state_url(&state_url))
+    }
+}
+
+impl AsyncBoxClient for Suggestionbox {
+    type Client = suggestionbox::Suggestionbox;
+
+    fn client(&self) -> &Arc<suggestionbox::Suggestionbox> {
+        &self.inner
+    }
+}
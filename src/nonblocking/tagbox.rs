@@ -0,0 +1,92 @@
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+use super::{spawn, spawn_dedicated, AsyncBoxClient, BoxFuture};
+use tagbox::{self, CheckResponse, Tag};
+use {Connection, ImageSource};
+
+/// The asynchronous client for the `tagbox` machine box.
+#[derive(Clone)]
+pub struct Tagbox {
+    inner: Arc<tagbox::Tagbox>,
+}
+
+impl Tagbox {
+    /// Creates a new tagbox client
+    pub fn new(url: &str) -> Tagbox {
+        Tagbox::with_connection(Connection::new(url))
+    }
+
+    /// Creates a new tagbox client that makes all of its requests through the
+    /// supplied connection
+    pub fn with_connection(conn: Connection) -> Tagbox {
+        Tagbox {
+            inner: Arc::new(tagbox::Tagbox::with_connection(conn)),
+        }
+    }
+
+    /// Gets the tags for the supplied image
+    pub fn check(&self, image: ImageSource) -> BoxFuture<CheckResponse> {
+        spawn(&self.inner, move |tb| tb.check(image))
+    }
+
+    /// Teaches the tagbox the supplied image with a custom tag
+    pub fn teach(&self, image: ImageSource, tag: &str, id: Option<&str>) -> BoxFuture<()> {
+        let (tag, id) = (tag.to_owned(), id.map(str::to_owned));
+        spawn(&self.inner, move |tb| tb.teach(image, &tag, id.as_deref()))
+    }
+
+    /// Deletes a custom tag by its ID
+    pub fn remove_custom_tag(&self, id: &str) -> BoxFuture<()> {
+        let id = id.to_owned();
+        spawn(&self.inner, move |tb| tb.remove_custom_tag(&id))
+    }
+
+    /// Renames a custom tag with the indicated ID
+    pub fn rename_custom_tag(&self, id: &str, tag: &str) -> BoxFuture<()> {
+        let (id, tag) = (id.to_owned(), tag.to_owned());
+        spawn(&self.inner, move |tb| tb.rename_custom_tag(&id, &tag))
+    }
+
+    /// Checks the supplied image for similar images based on previously taught tags
+    pub fn similar(&self, image: ImageSource) -> BoxFuture<Vec<Tag>> {
+        spawn(&self.inner, move |tb| tb.similar(image))
+    }
+
+    /// Downloads the state of the tagbox into `buf`, handing the buffer back along
+    /// with the number of bytes written
+    pub fn download_state<W>(&self, mut buf: W) -> BoxFuture<(W, u64)>
+    where
+        W: Write + Send + 'static,
+    {
+        spawn_dedicated(&self.inner, move |tb| {
+            let bytecount = tb.download_state(&mut buf)?;
+            Ok((buf, bytecount))
+        })
+    }
+
+    /// Submits the state file indicated by the `source_path` parameter to the tagbox
+    pub fn post_state(&self, source_path: &str) -> BoxFuture<()> {
+        let source_path = source_path.to_owned();
+        spawn_dedicated(&self.inner, move |tb| tb.post_state(&source_path))
+    }
+
+    /// Submits the state file read from `reader` to the tagbox
+    pub fn post_state_reader<R: Read + Send + 'static>(&self, reader: R) -> BoxFuture<()> {
+        spawn_dedicated(&self.inner, move |tb| tb.post_state_reader(reader))
+    }
+
+    /// Submits a state URL to the tagbox
+    pub fn post_state_url(&self, state_url: &str) -> BoxFuture<()> {
+        let state_url = state_url.to_owned();
+        spawn_dedicated(&self.inner, move |tb| tb.post_state_url(&state_url))
+    }
+}
+
+impl AsyncBoxClient for Tagbox {
+    type Client = tagbox::Tagbox;
+
+    fn client(&self) -> &Arc<tagbox::Tagbox> {
+        &self.inner
+    }
+}
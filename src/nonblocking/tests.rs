@@ -0,0 +1,230 @@
+extern crate futures_executor;
+extern crate mockito;
+
+use self::futures_executor::block_on;
+use self::mockito::{mock, Matcher, SERVER_URL};
+use super::{set_workers, spawn, spawn_dedicated, workers, AsyncBoxClient, Facebox, Suggestionbox,
+            Textbox, Videobox, MAX_TRANSFERS};
+use std::io::Cursor;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use videobox::{CheckOptionsBuilder, Status, WaitOptionsBuilder};
+use {ImageSource, Kind, Result};
+
+#[test]
+fn check_url_resolves_to_blocking_type() {
+    let fb = Facebox::new(SERVER_URL);
+    let mock = mock("POST", "/facebox/check")
+        .with_body(
+            r#"{
+    "success": true,
+    "facesCount": 1,
+    "faces": [
+        {
+            "rect": { "top": 0, "left": 0, "width": 120, "height": 120 },
+            "id": "john.jpg",
+            "name": "John Lennon",
+            "matched": true,
+            "confidence": 0.9
+        }
+    ]
+}"#,
+        )
+        .create();
+    {
+        let image = ImageSource::url("https://test.machinebox.io/thebeatles.jpg");
+        let res = block_on(fb.check(image)).unwrap();
+        assert_eq!(res.faces.len(), 1);
+        assert_eq!(res.faces[0].name, Some("John Lennon".to_owned()));
+    }
+    mock.assert();
+}
+
+#[test]
+fn errors_are_propagated() {
+    let sb = Suggestionbox::new(SERVER_URL);
+    let mock = mock("GET", "/suggestionbox/models/async-missing")
+        .with_status(404)
+        .create();
+    {
+        let err = block_on(sb.get_model("async-missing")).unwrap_err();
+        assert!(err.is_not_found());
+    }
+    mock.assert();
+}
+
+#[test]
+fn download_state_returns_buffer() {
+    let sb = Suggestionbox::new(SERVER_URL);
+    let mock = mock("GET", "/suggestionbox/state/async-model")
+        .with_body("statefile")
+        .create();
+    {
+        let (buf, bytecount) = block_on(sb.download_state("async-model", Vec::new())).unwrap();
+        assert_eq!(bytecount, 9);
+        assert_eq!(buf, b"statefile");
+    }
+    mock.assert();
+}
+
+#[test]
+fn post_state_reader_streams_state() {
+    let sb = Suggestionbox::new(SERVER_URL);
+    let upload = mock("POST", "/suggestionbox/state")
+        .match_body(Matcher::Regex("(?s)name=\"state\".*asyncstate".to_owned()))
+        .with_body(r#"{"success": true, "id": "async-model", "name": "Async", "choices": []}"#)
+        .create();
+    {
+        let model = block_on(sb.post_state_reader(Cursor::new(b"asyncstate".to_vec()))).unwrap();
+        assert_eq!(model.id, Some("async-model".to_owned()));
+    }
+    upload.assert();
+}
+
+#[test]
+fn check_and_wait_resolves_to_results() {
+    let vb = Videobox::new(SERVER_URL);
+    let check = mock("POST", "/videobox/check")
+        .with_body(r#"{"success": true, "id": "async-wait-complete"}"#)
+        .create();
+    let status = mock("GET", "/videobox/status/async-wait-complete")
+        .with_body(r#"{"success": true, "id": "async-wait-complete", "status": "complete"}"#)
+        .create();
+    let results = mock("GET", "/videobox/results/async-wait-complete")
+        .with_body(r#"{"success": true, "ready": true}"#)
+        .create();
+    {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let progress = Arc::clone(&seen);
+        let analysis = block_on(vb.check_and_wait(
+            "https://test.machinebox.io/video.mp4",
+            CheckOptionsBuilder::new().finish().unwrap(),
+            &WaitOptionsBuilder::new().finish(),
+            move |video| progress.lock().unwrap().push(video.status == Status::Complete),
+        ))
+        .unwrap();
+        assert!(analysis.ready);
+        assert_eq!(*seen.lock().unwrap(), vec![true]);
+    }
+    check.assert();
+    status.assert();
+    results.assert();
+}
+
+#[test]
+fn wait_pauses_between_polls_until_deadline() {
+    let vb = Videobox::new(SERVER_URL);
+    let _mock = mock("GET", "/videobox/status/async-wait-slow")
+        .with_body(r#"{"success": true, "id": "async-wait-slow", "status": "processing"}"#)
+        .create();
+    {
+        let opts = WaitOptionsBuilder::new()
+            .poll_interval(Duration::from_millis(10))
+            .deadline(Duration::from_millis(50))
+            .finish();
+        let polls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&polls);
+        let err = block_on(vb.wait("async-wait-slow", &opts, move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }))
+        .unwrap_err();
+        assert!(err.is_timeout());
+        assert!(polls.load(Ordering::SeqCst) >= 2);
+    }
+}
+
+#[test]
+fn probes_are_available() {
+    let tb = Textbox::new(SERVER_URL);
+    let mock = mock("GET", "/readyz").with_status(200).create();
+    {
+        assert!(block_on(tb.is_ready()).unwrap());
+    }
+    mock.assert();
+}
+
+#[test]
+fn panics_become_errors() {
+    let client = Arc::new(());
+    let err = block_on(spawn(&client, |_| -> Result<()> { panic!("bad request") })).unwrap_err();
+    match *err.kind() {
+        Kind::WorkerPanicked(ref message) => assert_eq!(message, "bad request"),
+        ref other => panic!("unexpected error kind: {:?}", other),
+    }
+    // The worker survived the panic, and the pool keeps serving requests
+    assert_eq!(block_on(spawn(&client, |_| Ok(7))).unwrap(), 7);
+}
+
+#[test]
+fn more_requests_than_workers_complete() {
+    let client = Arc::new(AtomicUsize::new(0));
+    let requests: Vec<_> = (0..workers() * 3)
+        .map(|_| {
+            spawn(&client, |count| {
+                thread::sleep(Duration::from_millis(10));
+                Ok(count.fetch_add(1, Ordering::SeqCst))
+            })
+        })
+        .collect();
+    for request in requests {
+        block_on(request).unwrap();
+    }
+    assert_eq!(client.load(Ordering::SeqCst), workers() * 3);
+}
+
+#[test]
+fn dedicated_requests_run_while_the_pool_is_busy() {
+    let (release, blocked) = mpsc::channel::<()>();
+    let blocked = Arc::new(Mutex::new(blocked));
+    let busy: Vec<_> = (0..workers())
+        .map(|_| {
+            spawn(&blocked, |blocked| {
+                let _ = blocked.lock().unwrap().recv();
+                Ok(())
+            })
+        })
+        .collect();
+    // Every worker is now held up, or about to be, yet a transfer still goes ahead
+    assert_eq!(block_on(spawn_dedicated(&Arc::new(()), |_| Ok(7))).unwrap(), 7);
+    for _ in 0..busy.len() {
+        release.send(()).unwrap();
+    }
+    for request in busy {
+        block_on(request).unwrap();
+    }
+}
+
+#[test]
+fn transfers_are_limited() {
+    // The number of transfers running, and the most that ever ran at once
+    let counts = Arc::new((AtomicUsize::new(0), AtomicUsize::new(0)));
+    let transfers: Vec<_> = (0..MAX_TRANSFERS * 3)
+        .map(|_| {
+            spawn_dedicated(&counts, |(running, most)| {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                most.fetch_max(now, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(10));
+                running.fetch_sub(1, Ordering::SeqCst);
+                Ok(())
+            })
+        })
+        .collect();
+    for transfer in transfers {
+        block_on(transfer).unwrap();
+    }
+    assert!(counts.1.load(Ordering::SeqCst) <= MAX_TRANSFERS);
+}
+
+#[test]
+fn pool_size_is_fixed_once_started() {
+    assert!(set_workers(0).is_err());
+    block_on(spawn(&Arc::new(()), |_| Ok(()))).unwrap();
+    let err = set_workers(workers() + 1).unwrap_err();
+    match *err.kind() {
+        Kind::InvalidOptions(_) => {}
+        ref other => panic!("unexpected error kind: {:?}", other),
+    }
+}
@@ -0,0 +1,40 @@
+use std::sync::Arc;
+
+use super::{spawn, AsyncBoxClient, BoxFuture};
+use textbox::{self, Analysis};
+use Connection;
+
+/// The asynchronous client for the `textbox` machine box.
+#[derive(Clone)]
+pub struct Textbox {
+    inner: Arc<textbox::Textbox>,
+}
+
+impl Textbox {
+    /// Creates a new textbox client
+    pub fn new(url: &str) -> Textbox {
+        Textbox::with_connection(Connection::new(url))
+    }
+
+    /// Creates a new textbox client that makes all of its requests through the
+    /// supplied connection
+    pub fn with_connection(conn: Connection) -> Textbox {
+        Textbox {
+            inner: Arc::new(textbox::Textbox::with_connection(conn)),
+        }
+    }
+
+    /// Analyzes the supplied text
+    pub fn check(&self, text: &str) -> BoxFuture<Analysis> {
+        let text = text.to_owned();
+        spawn(&self.inner, move |tb| tb.check(&text))
+    }
+}
+
+impl AsyncBoxClient for Textbox {
+    type Client = textbox::Textbox;
+
+    fn client(&self) -> &Arc<textbox::Textbox> {
+        &self.inner
+    }
+}
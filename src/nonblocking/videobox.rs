@@ -0,0 +1,204 @@
+use std::future::Future;
+use std::io::Read;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::Instant;
+
+use super::{delay, spawn, spawn_dedicated, AsyncBoxClient, BoxFuture, Delay};
+use videobox::{self, expired_if_missing, next_step, CheckOptions, Step, Video, VideoAnalysis,
+               WaitOptions};
+use {Connection, Result};
+
+/// The asynchronous client for the `videobox` machine box.
+#[derive(Clone)]
+pub struct Videobox {
+    inner: Arc<videobox::Videobox>,
+}
+
+impl Videobox {
+    /// Creates a new videobox client
+    pub fn new(url: &str) -> Videobox {
+        Videobox::with_connection(Connection::new(url))
+    }
+
+    /// Creates a new videobox client that makes all of its requests through the
+    /// supplied connection
+    pub fn with_connection(conn: Connection) -> Videobox {
+        Videobox {
+            inner: Arc::new(videobox::Videobox::with_connection(conn)),
+        }
+    }
+
+    /// Begins processing the video at the given URL
+    pub fn check_url(&self, video_url: &str, options: CheckOptions) -> BoxFuture<Video> {
+        let video_url = video_url.to_owned();
+        spawn(&self.inner, move |vb| vb.check_url(&video_url, options))
+    }
+
+    /// Uploads the video file at `source_path` and begins processing it
+    pub fn check_path<P: AsRef<Path>>(&self, source_path: P, options: CheckOptions) -> BoxFuture<Video> {
+        let source_path = source_path.as_ref().to_path_buf();
+        spawn_dedicated(&self.inner, move |vb| vb.check_path(&source_path, options))
+    }
+
+    /// Uploads the video read from `reader` and begins processing it
+    pub fn check_reader<T: Read + Send + 'static>(&self, reader: T, options: CheckOptions) -> BoxFuture<Video> {
+        spawn_dedicated(&self.inner, move |vb| vb.check_reader(reader, options))
+    }
+
+    /// Removes the processing results for a video
+    pub fn delete(&self, id: &str) -> BoxFuture<()> {
+        let id = id.to_owned();
+        spawn(&self.inner, move |vb| vb.delete(&id))
+    }
+
+    /// Gets the results of a completed video processing operation
+    pub fn results(&self, id: &str) -> BoxFuture<VideoAnalysis> {
+        let id = id.to_owned();
+        spawn(&self.inner, move |vb| vb.results(&id))
+    }
+
+    /// Checks the status of a video processing job
+    pub fn status(&self, id: &str) -> BoxFuture<Video> {
+        let id = id.to_owned();
+        spawn(&self.inner, move |vb| vb.status(&id))
+    }
+
+    /// Resolves once the video with the given ID has been processed, to its results. Each
+    /// status check is a short request on the worker pool, and no thread is held up
+    /// between checks. The `progress` callback is invoked by the task awaiting the wait
+    /// each time the status is polled.
+    pub fn wait<F>(&self, id: &str, options: &WaitOptions, progress: F) -> VideoWait<F>
+    where
+        F: FnMut(&Video) + Send + 'static,
+    {
+        let mut wait = VideoWait {
+            inner: Arc::clone(&self.inner),
+            id: id.to_owned(),
+            options: options.clone(),
+            progress,
+            started: Instant::now(),
+            stage: Stage::Done,
+        };
+        wait.stage = wait.poll_status();
+        wait
+    }
+
+    /// Begins processing the video at the given URL and resolves to its results once
+    /// they are available, as `check_url` followed by `wait` would
+    pub fn check_and_wait<F>(&self, video_url: &str, check: CheckOptions, options: &WaitOptions,
+                             progress: F) -> VideoWait<F>
+    where
+        F: FnMut(&Video) + Send + 'static,
+    {
+        VideoWait {
+            inner: Arc::clone(&self.inner),
+            id: String::new(),
+            options: options.clone(),
+            progress,
+            started: Instant::now(),
+            stage: Stage::Checking(self.check_url(video_url, check)),
+        }
+    }
+}
+
+/// A wait for a video to be processed, as returned by `Videobox::wait` and
+/// `Videobox::check_and_wait`. Resolves to the video's results, or fails as the
+/// blocking `videobox::Videobox::wait` would.
+///
+/// The first request of the wait is sent as soon as it is created, but every later status
+/// check, pause and the final fetch of the results only happen while the wait is polled.
+#[must_use = "a wait only gets past its first request while it is polled"]
+pub struct VideoWait<F> {
+    inner: Arc<videobox::Videobox>,
+    id: String,
+    options: WaitOptions,
+    progress: F,
+    started: Instant,
+    stage: Stage,
+}
+
+/// The step of a `VideoWait` that is in progress
+enum Stage {
+    Checking(BoxFuture<Video>),
+    Polling(BoxFuture<Video>),
+    Pausing(Delay),
+    Fetching(BoxFuture<VideoAnalysis>),
+    Done,
+}
+
+// The progress callback is never pinned, so the wait can move freely
+impl<F> Unpin for VideoWait<F> {}
+
+impl<F> VideoWait<F> {
+    fn poll_status(&self) -> Stage {
+        let id = self.id.clone();
+        Stage::Polling(spawn(&self.inner, move |vb| {
+            vb.status(&id).map_err(|e| expired_if_missing(e, &id))
+        }))
+    }
+
+    fn fetch_results(&self) -> Stage {
+        let id = self.id.clone();
+        Stage::Fetching(spawn(&self.inner, move |vb| {
+            vb.results(&id).map_err(|e| expired_if_missing(e, &id))
+        }))
+    }
+}
+
+impl<F: FnMut(&Video)> Future for VideoWait<F> {
+    type Output = Result<VideoAnalysis>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<VideoAnalysis>> {
+        let wait = &mut *self;
+        loop {
+            let outcome = match wait.stage {
+                Stage::Checking(ref mut check) => match ready!(Pin::new(check).poll(cx)) {
+                    Ok(video) => {
+                        wait.id = video.id;
+                        wait.started = Instant::now();
+                        Ok(wait.poll_status())
+                    }
+                    Err(e) => Err(e),
+                },
+                Stage::Polling(ref mut status) => match ready!(Pin::new(status).poll(cx)) {
+                    Ok(video) => {
+                        (wait.progress)(&video);
+                        match next_step(&video, &wait.id, &wait.options, wait.started) {
+                            Ok(Step::Results) => Ok(wait.fetch_results()),
+                            Ok(Step::Pause(pause)) => Ok(Stage::Pausing(delay(pause))),
+                            Err(e) => Err(e),
+                        }
+                    }
+                    Err(e) => Err(e),
+                },
+                Stage::Pausing(ref mut pause) => {
+                    ready!(Pin::new(pause).poll(cx)).map(|()| wait.poll_status())
+                }
+                Stage::Fetching(ref mut results) => {
+                    let results = ready!(Pin::new(results).poll(cx));
+                    wait.stage = Stage::Done;
+                    return Poll::Ready(results);
+                }
+                Stage::Done => panic!("a video wait was polled after it resolved"),
+            };
+            match outcome {
+                Ok(stage) => wait.stage = stage,
+                Err(e) => {
+                    wait.stage = Stage::Done;
+                    return Poll::Ready(Err(e));
+                }
+            }
+        }
+    }
+}
+
+impl AsyncBoxClient for Videobox {
+    type Client = videobox::Videobox;
+
+    fn client(&self) -> &Arc<videobox::Videobox> {
+        &self.inner
+    }
+}
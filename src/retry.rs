@@ -0,0 +1,197 @@
+use reqwest::StatusCode;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use super::{Error, Kind};
+
+/// The HTTP statuses with which a box reports a failure that is likely to be transient:
+/// too many requests, or a box that is overloaded or still starting up
+pub(crate) const TRANSIENT_STATUSES: [u16; 4] = [429, 502, 503, 504];
+
+/// Determines whether, and how, requests that fail for transient reasons are retried.
+///
+/// A request is retried when the box answers with one of the `retryable_statuses` (boxes
+/// return 503 while warming up), when the connection to the box fails, or when the request
+/// times out. Between attempts the client sleeps for an exponentially increasing backoff,
+/// optionally reduced by a random jitter so that many clients don't retry in lockstep.
+///
+/// Operations that are not safe to repeat, such as `Suggestionbox::reward` or
+/// `Facebox::teach`, are only retried when `retry_non_idempotent` is set. Uploading state is
+/// never retried, as it replaces the state of the box or creates a model, and neither are
+/// operations that stream their payload from a reader, as the payload cannot be replayed.
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use machinebox::{Connection, RetryPolicy};
+///
+/// let policy = RetryPolicy {
+///     max_attempts: 5,
+///     initial_backoff: Duration::from_millis(250),
+///     ..RetryPolicy::default()
+/// };
+/// let conn = Connection::builder("http://localhost:8080")
+///     .retry_policy(policy)
+///     .finish()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// The maximum number of times a request will be attempted, including the first attempt
+    pub max_attempts: u32,
+    /// The amount of time to wait before the first retry
+    pub initial_backoff: Duration,
+    /// The upper bound on the amount of time to wait between attempts
+    pub max_backoff: Duration,
+    /// The factor by which the backoff grows after each attempt
+    pub multiplier: f64,
+    /// The fraction (between 0 and 1) of each backoff that may randomly be skipped
+    pub jitter: f64,
+    /// The HTTP status codes that indicate a transient failure. By default these are 429,
+    /// 502, 503 and 504, the statuses for which `Error::is_retryable` is true.
+    pub retryable_statuses: Vec<u16>,
+    /// Allows operations that are not safe to repeat to be retried
+    pub retry_non_idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            multiplier: 2.0,
+            jitter: 0.2,
+            retryable_statuses: TRANSIENT_STATUSES.to_vec(),
+            retry_non_idempotent: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries a request
+    pub fn never() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// The number of attempts allowed for a request that may be repeated as indicated
+    pub(crate) fn attempts(&self, retry: Retry) -> u32 {
+        let allowed = match retry {
+            Retry::Idempotent => true,
+            Retry::NonIdempotent => self.retry_non_idempotent,
+            Retry::Never => false,
+        };
+        if allowed {
+            self.max_attempts.max(1)
+        } else {
+            1
+        }
+    }
+
+    /// Indicates whether a response with the given status should be retried
+    pub(crate) fn retries_status(&self, status: StatusCode) -> bool {
+        self.retryable_statuses.contains(&status.as_u16())
+    }
+
+    /// Indicates whether a request that failed with the given error should be retried
+    pub(crate) fn retries_error(&self, e: &Error) -> bool {
+        matches!(e.kind, Kind::Transport(_) | Kind::Timeout)
+    }
+
+    /// The amount of time to wait after the given (1-based) failed attempt
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1) as i32;
+        let base = self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent);
+        let capped = base.min(self.max_backoff.as_secs_f64()).max(0.0);
+        let jitter = self.jitter.clamp(0.0, 1.0) * random_fraction();
+        Duration::from_secs_f64(capped * (1.0 - jitter))
+    }
+}
+
+/// Describes whether a request may safely be sent more than once
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Retry {
+    /// The request can be repeated without side effects
+    Idempotent,
+    /// Repeating the request may have side effects, such as a duplicate reward
+    NonIdempotent,
+    /// The request body cannot be replayed
+    Never,
+}
+
+/// A random number in `[0, 1)`, drawn from the randomly keyed standard hasher
+fn random_fraction() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Retry, RetryPolicy};
+    use reqwest::StatusCode;
+    use std::time::Duration;
+    use {Error, Kind};
+
+    #[test]
+    fn backoff_grows_exponentially_up_to_the_cap() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(300),
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(300));
+        assert_eq!(policy.backoff(10), Duration::from_millis(300));
+    }
+
+    #[test]
+    fn jitter_only_shortens_backoff() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_millis(100),
+            jitter: 0.5,
+            ..RetryPolicy::default()
+        };
+        for _ in 0..100 {
+            let backoff = policy.backoff(1);
+            assert!(backoff >= Duration::from_millis(50));
+            assert!(backoff <= Duration::from_millis(100));
+        }
+    }
+
+    #[test]
+    fn non_idempotent_requests_require_opt_in() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.attempts(Retry::Idempotent), 3);
+        assert_eq!(policy.attempts(Retry::NonIdempotent), 1);
+        assert_eq!(policy.attempts(Retry::Never), 1);
+
+        let policy = RetryPolicy {
+            retry_non_idempotent: true,
+            ..RetryPolicy::default()
+        };
+        assert_eq!(policy.attempts(Retry::NonIdempotent), 3);
+        assert_eq!(policy.attempts(Retry::Never), 1);
+    }
+
+    #[test]
+    fn default_statuses_match_retryable_errors() {
+        let policy = RetryPolicy::default();
+        for status in 400..600 {
+            let e = Error::from(Kind::Http {
+                status,
+                body: String::new(),
+            });
+            assert_eq!(
+                policy.retries_status(StatusCode::try_from(status).unwrap()),
+                e.is_retryable(),
+                "status {}",
+                status
+            );
+        }
+    }
+}
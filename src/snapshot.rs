@@ -0,0 +1,696 @@
+//! Backups of the state of facebox, tagbox and suggestionbox
+//!
+//! A `SnapshotManager` downloads the state of a box into a timestamped file in a
+//! directory, and records each file in a JSON manifest alongside the box's name, build
+//! and version, the size of the state and its SHA-256 checksum. Only the most recent
+//! snapshots of each box are kept. A snapshot is checked against its checksum before it
+//! is uploaded back to a box, and a manifest that names a file outside its directory is
+//! rejected before any file is read or removed.
+use chrono::{DateTime, Utc};
+use serde_json;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+use facebox::Facebox;
+use suggestionbox::Suggestionbox;
+use tagbox::Tagbox;
+use utils::parse;
+use {BoxClient, BoxInfo, Error, Kind, Result};
+
+/// The name of the manifest file within a snapshot directory
+const MANIFEST: &str = "manifest.json";
+
+/// The name suggestionbox reports in its `info`
+const SUGGESTIONBOX: &str = "suggestionbox";
+
+/// A box whose state can be downloaded and restored by a `SnapshotManager`
+pub trait Stateful {
+    /// Provides information about the box
+    fn box_info(&self) -> Result<BoxInfo>;
+
+    /// The model the state belongs to, for boxes that keep a separate state per model
+    fn model_id(&self) -> Option<&str> {
+        None
+    }
+
+    /// Writes the state of the box to `buf`, returning the number of bytes written
+    fn save_state(&self, buf: &mut dyn Write) -> Result<u64>;
+
+    /// Replaces the state of the box with the state read from `state`, which `snapshot`
+    /// records. For boxes with a separate state per model, this restores the state of the
+    /// model `model_id` refers to, and fails with `Kind::SnapshotMismatch` without uploading
+    /// anything if `snapshot` was taken from a different model.
+    fn restore_state(&self, snapshot: &Snapshot, state: File) -> Result<()>;
+}
+
+impl Stateful for Facebox {
+    fn box_info(&self) -> Result<BoxInfo> {
+        self.info()
+    }
+
+    fn save_state(&self, mut buf: &mut dyn Write) -> Result<u64> {
+        self.download_state(&mut buf)
+    }
+
+    fn restore_state(&self, _snapshot: &Snapshot, state: File) -> Result<()> {
+        self.post_state_reader(state)
+    }
+}
+
+impl Stateful for Tagbox {
+    fn box_info(&self) -> Result<BoxInfo> {
+        self.info()
+    }
+
+    fn save_state(&self, mut buf: &mut dyn Write) -> Result<u64> {
+        self.download_state(&mut buf)
+    }
+
+    fn restore_state(&self, _snapshot: &Snapshot, state: File) -> Result<()> {
+        self.post_state_reader(state)
+    }
+}
+
+/// The state of a single suggestionbox model.
+///
+/// Suggestionbox creates the model again from a restored state, and refuses to do so while
+/// a model with the same ID exists. Delete the model before restoring a snapshot of it.
+pub struct ModelState<'a> {
+    client: &'a Suggestionbox,
+    model_id: String,
+}
+
+impl<'a> ModelState<'a> {
+    /// Refers to the state of the given model
+    pub fn new(client: &'a Suggestionbox, model_id: &str) -> ModelState<'a> {
+        ModelState {
+            client,
+            model_id: model_id.to_owned(),
+        }
+    }
+}
+
+impl<'a> Stateful for ModelState<'a> {
+    fn box_info(&self) -> Result<BoxInfo> {
+        self.client.info()
+    }
+
+    fn model_id(&self) -> Option<&str> {
+        Some(&self.model_id)
+    }
+
+    fn save_state(&self, mut buf: &mut dyn Write) -> Result<u64> {
+        self.client.download_state(&self.model_id, &mut buf)
+    }
+
+    fn restore_state(&self, snapshot: &Snapshot, state: File) -> Result<()> {
+        if !snapshot.is_of(SUGGESTIONBOX, Some(&self.model_id)) {
+            return Err(Error::from(Kind::SnapshotMismatch {
+                name: snapshot.name.clone(),
+                model_id: snapshot.model_id.clone(),
+                target_name: SUGGESTIONBOX.to_owned(),
+                target_model_id: Some(self.model_id.clone()),
+            }));
+        }
+        self.client.post_state_reader(state).map(|_| ())
+    }
+}
+
+/// A state file taken by a `SnapshotManager`, as recorded in its manifest
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    /// The name of the state file within the snapshot directory
+    pub file: String,
+    /// The name of the box, as reported by its `info`
+    pub name: String,
+    /// The model the state belongs to, if the box keeps a separate state per model
+    #[serde(default)]
+    pub model_id: Option<String>,
+    /// The build of the box
+    pub build: String,
+    /// The version of the box
+    pub version: u64,
+    /// The size of the state in bytes
+    pub size: u64,
+    /// The SHA-256 checksum of the state, in lowercase hex
+    pub sha256: String,
+    /// When the snapshot was taken
+    pub taken: DateTime<Utc>,
+}
+
+impl Snapshot {
+    fn is_of(&self, name: &str, model_id: Option<&str>) -> bool {
+        self.name == name && self.model_id.as_deref() == model_id
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Manifest {
+    snapshots: Vec<Snapshot>,
+}
+
+/// Takes, rotates and restores snapshots of box state in a directory.
+/// # Examples
+/// ```no_run
+/// use machinebox::facebox::Facebox;
+/// use machinebox::snapshot::{ModelState, SnapshotManager};
+/// use machinebox::suggestionbox::Suggestionbox;
+///
+/// let snapshots = SnapshotManager::new("/var/backups/machinebox", 7);
+/// snapshots.take(&Facebox::new("http://localhost:8080")).unwrap();
+///
+/// let sb = Suggestionbox::new("http://localhost:8081");
+/// snapshots.take(&ModelState::new(&sb, "articles")).unwrap();
+///
+/// // ...later, put the most recent facebox state back
+/// let fb = Facebox::new("http://localhost:8080");
+/// if let Some(latest) = snapshots.latest("facebox", None).unwrap() {
+///     snapshots.restore(&fb, &latest).unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SnapshotManager {
+    dir: PathBuf,
+    keep: usize,
+}
+
+impl SnapshotManager {
+    /// Creates a manager that keeps its snapshots in `dir`, and keeps the last `keep`
+    /// snapshots of each box and model. At least one snapshot is always kept.
+    pub fn new<P: Into<PathBuf>>(dir: P, keep: usize) -> SnapshotManager {
+        SnapshotManager {
+            dir: dir.into(),
+            keep: keep.max(1),
+        }
+    }
+
+    /// The directory the snapshots are kept in
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Downloads the state of a box into a new snapshot, records it in the manifest and
+    /// removes the snapshots of the same box and model that are no longer kept. The
+    /// directory is created if it doesn't exist yet. Nothing is downloaded if the manifest
+    /// can't be read, and the new state file is removed again if the manifest can't be
+    /// updated, so no file is left behind that the manifest doesn't know about.
+    pub fn take<S: Stateful>(&self, source: &S) -> Result<Snapshot> {
+        let info = source.box_info()?;
+        let model_id = source.model_id().map(str::to_owned);
+        fs::create_dir_all(&self.dir)?;
+        let mut manifest = self.load()?;
+
+        let taken = DateTime::<Utc>::from(SystemTime::now());
+        let (file, staging, handle) = self.create_staging(&info.name, model_id.as_ref(), taken)?;
+        let mut writer = HashingWriter::new(BufWriter::new(handle));
+        let saved = source
+            .save_state(&mut writer)
+            .and_then(|_| writer.flush().map_err(Error::from));
+        if let Err(e) = saved {
+            let _ = fs::remove_file(&staging);
+            return Err(e);
+        }
+        let (size, sha256) = writer.finish();
+        let path = self.dir.join(&file);
+        if let Err(e) = fs::rename(&staging, &path) {
+            let _ = fs::remove_file(&staging);
+            return Err(Error::from(e));
+        }
+
+        let snapshot = Snapshot {
+            file,
+            name: info.name,
+            model_id,
+            build: info.build,
+            version: info.version,
+            size,
+            sha256,
+            taken,
+        };
+        manifest.snapshots.push(snapshot.clone());
+        let removed = self.rotate(&mut manifest, &snapshot);
+        if let Err(e) = self.save(&manifest) {
+            let _ = fs::remove_file(&path);
+            return Err(e);
+        }
+        for old in removed {
+            match fs::remove_file(self.dir.join(&old.file)) {
+                Ok(()) => {}
+                Err(ref e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(Error::from(e)),
+            }
+        }
+        Ok(snapshot)
+    }
+
+    /// All snapshots in the manifest, oldest first
+    pub fn snapshots(&self) -> Result<Vec<Snapshot>> {
+        Ok(self.load()?.snapshots)
+    }
+
+    /// The snapshots of one box, and of one model for suggestionbox, oldest first
+    pub fn history(&self, name: &str, model_id: Option<&str>) -> Result<Vec<Snapshot>> {
+        let mut snapshots = self.snapshots()?;
+        snapshots.retain(|snapshot| snapshot.is_of(name, model_id));
+        Ok(snapshots)
+    }
+
+    /// The most recent snapshot of one box, and of one model for suggestionbox
+    pub fn latest(&self, name: &str, model_id: Option<&str>) -> Result<Option<Snapshot>> {
+        Ok(self.history(name, model_id)?.pop())
+    }
+
+    /// Checks that the state file of a snapshot still has the size and checksum recorded
+    /// in the manifest. Fails with `Kind::ChecksumMismatch` if it doesn't.
+    pub fn verify(&self, snapshot: &Snapshot) -> Result<()> {
+        let path = self.dir.join(&snapshot.file);
+        let state = File::open(&path)?;
+        check(snapshot, path, &state)
+    }
+
+    /// Verifies a snapshot and uploads it to a box. Nothing is uploaded if the snapshot
+    /// fails verification, or if it was taken from a different kind of box or a different
+    /// model than `target`, which fails with `Kind::SnapshotMismatch`. The state file is
+    /// opened once, and the upload reads the same handle that was verified.
+    pub fn restore<S: Stateful>(&self, target: &S, snapshot: &Snapshot) -> Result<()> {
+        let path = self.dir.join(&snapshot.file);
+        let mut state = File::open(&path)?;
+        check(snapshot, path, &state)?;
+        let info = target.box_info()?;
+        if !snapshot.is_of(&info.name, target.model_id()) {
+            return Err(Error::from(Kind::SnapshotMismatch {
+                name: snapshot.name.clone(),
+                model_id: snapshot.model_id.clone(),
+                target_name: info.name,
+                target_model_id: target.model_id().map(str::to_owned),
+            }));
+        }
+        state.seek(SeekFrom::Start(0))?;
+        target.restore_state(snapshot, state)
+    }
+
+    /// Creates a staging file for a new snapshot, returning the name the snapshot will
+    /// have once it is complete along with the path and handle of the staging file. Names
+    /// are made unique should two snapshots of the same box be taken within the same
+    /// millisecond.
+    fn create_staging(&self, name: &str, model_id: Option<&String>, taken: DateTime<Utc>)
+                      -> Result<(String, PathBuf, File)> {
+        let mut stem = sanitize(name);
+        if let Some(model_id) = model_id {
+            stem.push('-');
+            stem.push_str(&sanitize(model_id));
+        }
+        stem.push('-');
+        stem.push_str(&taken.format("%Y%m%dT%H%M%S%.3fZ").to_string());
+
+        let mut attempt = 0;
+        loop {
+            let file = if attempt == 0 {
+                format!("{}.state", stem)
+            } else {
+                format!("{}-{}.state", stem, attempt)
+            };
+            let staging = self.dir.join(format!("{}.tmp", file));
+            if !self.dir.join(&file).exists() {
+                match OpenOptions::new().write(true).create_new(true).open(&staging) {
+                    Ok(handle) => return Ok((file, staging, handle)),
+                    Err(ref e) if e.kind() == ErrorKind::AlreadyExists => {}
+                    Err(e) => return Err(Error::from(e)),
+                }
+            }
+            attempt += 1;
+        }
+    }
+
+    /// Drops the snapshots of the same box and model as `latest` beyond the number kept,
+    /// returning the ones that were dropped
+    fn rotate(&self, manifest: &mut Manifest, latest: &Snapshot) -> Vec<Snapshot> {
+        let model_id = latest.model_id.as_deref();
+        let count = manifest
+            .snapshots
+            .iter()
+            .filter(|snapshot| snapshot.is_of(&latest.name, model_id))
+            .count();
+        let mut excess = count.saturating_sub(self.keep);
+        let mut removed = Vec::new();
+        manifest.snapshots.retain(|snapshot| {
+            if excess > 0 && snapshot.is_of(&latest.name, model_id) {
+                excess -= 1;
+                removed.push(snapshot.clone());
+                false
+            } else {
+                true
+            }
+        });
+        removed
+    }
+
+    /// Reads the manifest. A missing one is empty, one that can't be parsed fails with
+    /// `Kind::Deserialization`, and one that names a file outside the directory fails with
+    /// `Kind::InvalidSnapshotFile`.
+    fn load(&self) -> Result<Manifest> {
+        let path = self.dir.join(MANIFEST);
+        let manifest: Manifest = match fs::read_to_string(&path) {
+            Ok(contents) => parse(&contents)?,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(Manifest::default()),
+            Err(e) => return Err(Error::from(e)),
+        };
+        if let Some(snapshot) = manifest.snapshots.iter().find(|s| !is_state_file(&s.file)) {
+            return Err(Error::from(Kind::InvalidSnapshotFile {
+                manifest: path,
+                file: snapshot.file.clone(),
+            }));
+        }
+        Ok(manifest)
+    }
+
+    /// Replaces the manifest atomically, so a crash never leaves a truncated one behind
+    fn save(&self, manifest: &Manifest) -> Result<()> {
+        let path = self.dir.join(MANIFEST);
+        let staging = self.dir.join(format!("{}.tmp", MANIFEST));
+        {
+            let mut writer = BufWriter::new(File::create(&staging)?);
+            serde_json::to_writer_pretty(&mut writer, manifest)?;
+            writer.flush()?;
+        }
+        fs::rename(&staging, path)?;
+        Ok(())
+    }
+}
+
+/// Checks the state read from `state` against the size and checksum recorded for a
+/// snapshot, failing with `Kind::ChecksumMismatch` if they differ
+fn check(snapshot: &Snapshot, path: PathBuf, state: &File) -> Result<()> {
+    let mut writer = HashingWriter::new(io::sink());
+    io::copy(&mut BufReader::new(state), &mut writer)?;
+    let (size, sha256) = writer.finish();
+    if size != snapshot.size || sha256 != snapshot.sha256 {
+        return Err(Error::from(Kind::ChecksumMismatch {
+            path,
+            expected: snapshot.sha256.clone(),
+            actual: sha256,
+        }));
+    }
+    Ok(())
+}
+
+/// Whether `file` names a file directly within the snapshot directory, other than the
+/// manifest itself
+fn is_state_file(file: &str) -> bool {
+    let mut components = Path::new(file).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(name)), None) => name == file && file != MANIFEST,
+        _ => false,
+    }
+}
+
+/// Replaces the characters of a box name or model ID that don't belong in a file name
+fn sanitize(s: &str) -> String {
+    s.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '.' { c } else { '_' })
+        .collect()
+}
+
+/// Passes writes through while counting and hashing them
+struct HashingWriter<W> {
+    inner: W,
+    size: u64,
+    hasher: Sha256,
+}
+
+impl<W: Write> HashingWriter<W> {
+    fn new(inner: W) -> HashingWriter<W> {
+        HashingWriter {
+            inner,
+            size: 0,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// The number of bytes written, and their SHA-256 checksum in lowercase hex
+    fn finish(self) -> (u64, String) {
+        let digest = self.hasher.finalize();
+        let hex = digest.iter().map(|b| format!("{:02x}", b)).collect();
+        (self.size, hex)
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate mockito;
+
+    use self::mockito::{mock, SERVER_URL};
+    use super::{ModelState, Snapshot, SnapshotManager, Stateful};
+    use std::cell::RefCell;
+    use std::env;
+    use std::fs::{self, File, OpenOptions};
+    use std::io::{Read, Write};
+    use std::path::PathBuf;
+    use serde_json;
+    use std::process;
+    use suggestionbox::Suggestionbox;
+    use {BoxInfo, Kind, Result};
+
+    /// A box that hands out its state and remembers the last state restored to it
+    struct FakeBox {
+        model_id: Option<&'static str>,
+        state: RefCell<Vec<u8>>,
+        restored: RefCell<Option<Vec<u8>>>,
+    }
+
+    impl FakeBox {
+        fn new(model_id: Option<&'static str>, state: &[u8]) -> FakeBox {
+            FakeBox {
+                model_id,
+                state: RefCell::new(state.to_vec()),
+                restored: RefCell::new(None),
+            }
+        }
+    }
+
+    impl Stateful for FakeBox {
+        fn box_info(&self) -> Result<BoxInfo> {
+            Ok(BoxInfo {
+                success: true,
+                name: "suggestionbox".to_owned(),
+                version: 1,
+                build: "abc123".to_owned(),
+                status: "ready".to_owned(),
+                plan: "developer".to_owned(),
+                error: None,
+            })
+        }
+
+        fn model_id(&self) -> Option<&str> {
+            self.model_id
+        }
+
+        fn save_state(&self, buf: &mut dyn Write) -> Result<u64> {
+            buf.write_all(&self.state.borrow())?;
+            Ok(self.state.borrow().len() as u64)
+        }
+
+        fn restore_state(&self, _snapshot: &Snapshot, mut state: File) -> Result<()> {
+            let mut restored = Vec::new();
+            state.read_to_end(&mut restored)?;
+            *self.restored.borrow_mut() = Some(restored);
+            Ok(())
+        }
+    }
+
+    /// A directory that doesn't exist yet, unique to this test and this test run
+    fn empty_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn takes_and_rotates_snapshots() {
+        let dir = empty_dir("machinebox-snapshots-rotate");
+        let snapshots = SnapshotManager::new(&dir, 2);
+        let articles = FakeBox::new(Some("articles"), b"first");
+        let movies = FakeBox::new(Some("movies"), b"movies");
+
+        let first = snapshots.take(&articles).unwrap();
+        assert_eq!(first.name, "suggestionbox");
+        assert_eq!(first.model_id, Some("articles".to_owned()));
+        assert_eq!((first.build.as_str(), first.version, first.size), ("abc123", 1, 5));
+        assert_eq!(
+            first.sha256,
+            "a7937b64b8caa58f03721bb6bacf5c78cb235febe0e70b1b84cd99541461a08e"
+        );
+        assert!(first.file.starts_with("suggestionbox-articles-"));
+        assert_eq!(fs::read(dir.join(&first.file)).unwrap(), b"first");
+
+        snapshots.take(&movies).unwrap();
+        *articles.state.borrow_mut() = b"second".to_vec();
+        let second = snapshots.take(&articles).unwrap();
+        *articles.state.borrow_mut() = b"third".to_vec();
+        let third = snapshots.take(&articles).unwrap();
+        assert_ne!(second.file, third.file);
+
+        let history = snapshots.history("suggestionbox", Some("articles")).unwrap();
+        assert_eq!(history, vec![second, third.clone()]);
+        assert!(!dir.join(&first.file).exists());
+        assert_eq!(snapshots.history("suggestionbox", Some("movies")).unwrap().len(), 1);
+        assert_eq!(snapshots.latest("suggestionbox", Some("articles")).unwrap(), Some(third));
+        assert_eq!(snapshots.latest("facebox", None).unwrap(), None);
+
+        fs::write(dir.join("manifest.json"), "{\"snapshots\": [").unwrap();
+        match *snapshots.snapshots().unwrap_err().kind() {
+            Kind::Deserialization { ref body, .. } => assert_eq!(body, "{\"snapshots\": ["),
+            ref other => panic!("unexpected error kind: {:?}", other),
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn restores_only_verified_snapshots() {
+        let dir = empty_dir("machinebox-snapshots-restore");
+        let snapshots = SnapshotManager::new(&dir, 3);
+        let source = FakeBox::new(None, b"facestate");
+        let snapshot = snapshots.take(&source).unwrap();
+
+        let target = FakeBox::new(None, b"");
+        snapshots.restore(&target, &snapshot).unwrap();
+        assert_eq!(target.restored.borrow_mut().take(), Some(b"facestate".to_vec()));
+
+        OpenOptions::new()
+            .append(true)
+            .open(dir.join(&snapshot.file))
+            .unwrap()
+            .write_all(b"!")
+            .unwrap();
+        match *snapshots.restore(&target, &snapshot).unwrap_err().kind() {
+            Kind::ChecksumMismatch { ref path, ref expected, .. } => {
+                assert_eq!(path, &dir.join(&snapshot.file));
+                assert_eq!(expected, &snapshot.sha256);
+            }
+            ref other => panic!("unexpected error kind: {:?}", other),
+        }
+        assert!(target.restored.borrow().is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_manifests_naming_other_paths() {
+        let dir = empty_dir("machinebox-snapshots-paths");
+        let snapshots = SnapshotManager::new(&dir, 1);
+        let source = FakeBox::new(None, b"facestate");
+        let snapshot = snapshots.take(&source).unwrap();
+        let manifest = fs::read_to_string(dir.join("manifest.json")).unwrap();
+        let outside = dir.with_extension("outside");
+        fs::write(&outside, b"keep me").unwrap();
+
+        let escapes = format!("../{}", outside.file_name().unwrap().to_str().unwrap());
+        for file in &[escapes.as_str(), "sub/facebox.state", "/etc/passwd", "..", "manifest.json"] {
+            let json = serde_json::to_string(file).unwrap();
+            let tampered = manifest.replace(&format!("\"{}\"", snapshot.file), &json);
+            fs::write(dir.join("manifest.json"), tampered).unwrap();
+            match *snapshots.snapshots().unwrap_err().kind() {
+                Kind::InvalidSnapshotFile { file: ref recorded, .. } => assert_eq!(recorded, file),
+                ref other => panic!("unexpected error kind: {:?}", other),
+            }
+            // Rotation would otherwise remove the file the manifest names
+            assert!(snapshots.take(&source).is_err());
+            assert!(outside.exists());
+        }
+
+        fs::remove_file(&outside).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failed_snapshots_leave_no_files() {
+        let dir = empty_dir("machinebox-snapshots-failed");
+        let snapshots = SnapshotManager::new(&dir, 3);
+        let source = FakeBox::new(None, b"facestate");
+        let state_files = || {
+            fs::read_dir(&dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| path.extension() == Some("state".as_ref()))
+                .count()
+        };
+
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("manifest.json"), "not a manifest").unwrap();
+        assert!(snapshots.take(&source).is_err());
+        assert_eq!(state_files(), 0);
+
+        // The manifest can't be replaced while a directory is in the way of its staging file
+        fs::remove_file(dir.join("manifest.json")).unwrap();
+        fs::create_dir(dir.join("manifest.json.tmp")).unwrap();
+        assert!(snapshots.take(&source).is_err());
+        assert_eq!(state_files(), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn restores_only_to_the_same_box_and_model() {
+        let dir = empty_dir("machinebox-snapshots-mismatch");
+        let snapshots = SnapshotManager::new(&dir, 3);
+        let snapshot = snapshots.take(&FakeBox::new(Some("articles"), b"articles")).unwrap();
+
+        for target in &[FakeBox::new(Some("movies"), b""), FakeBox::new(None, b"")] {
+            match *snapshots.restore(target, &snapshot).unwrap_err().kind() {
+                Kind::SnapshotMismatch { ref model_id, ref target_model_id, .. } => {
+                    assert_eq!(model_id.as_deref(), Some("articles"));
+                    assert_eq!(target_model_id.as_deref(), target.model_id);
+                }
+                ref other => panic!("unexpected error kind: {:?}", other),
+            }
+            assert!(target.restored.borrow().is_none());
+        }
+
+        let target = FakeBox::new(Some("articles"), b"");
+        snapshots.restore(&target, &snapshot).unwrap();
+        assert_eq!(target.restored.borrow_mut().take(), Some(b"articles".to_vec()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn model_state_checks_the_model_before_uploading() {
+        let dir = empty_dir("machinebox-snapshots-model");
+        let snapshots = SnapshotManager::new(&dir, 3);
+        let snapshot = snapshots.take(&FakeBox::new(Some("articles"), b"articles")).unwrap();
+
+        let sb = Suggestionbox::new(SERVER_URL);
+        let upload = mock("POST", "/suggestionbox/state").expect(0).create();
+        {
+            let state = File::open(dir.join(&snapshot.file)).unwrap();
+            let err = ModelState::new(&sb, "movies").restore_state(&snapshot, state).unwrap_err();
+            match *err.kind() {
+                Kind::SnapshotMismatch { ref model_id, ref target_model_id, .. } => {
+                    assert_eq!(model_id.as_deref(), Some("articles"));
+                    assert_eq!(target_model_id.as_deref(), Some("movies"));
+                }
+                ref other => panic!("unexpected error kind: {:?}", other),
+            }
+        }
+        upload.assert();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
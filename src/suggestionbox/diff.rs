@@ -0,0 +1,114 @@
+use super::{Choice, Feature, Model};
+
+/// A single change to the choices of a model
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChoiceChange {
+    /// A choice that should be added to the model
+    Add(Choice),
+    /// The ID of a choice that should be removed from the model
+    Remove(String),
+    /// A choice whose features should be replaced
+    ReplaceFeatures {
+        /// The ID of the choice
+        id: String,
+        /// The features the choice should have
+        features: Vec<Feature>,
+    },
+}
+
+/// The changes that would bring a model held by suggestionbox in line with a local copy
+/// of it.
+///
+/// suggestionbox has no endpoints for adding, removing or changing the choices of an
+/// existing model, so a diff can't be applied to a model on the box. Use it to see how a
+/// model has drifted from its local definition, and `apply` to produce the changed model
+/// locally, for example to create it under a new ID alongside the original.
+/// # Examples
+/// ```no_run
+/// use machinebox::suggestionbox::{Model, ModelDiff, Suggestionbox};
+/// use std::fs::File;
+///
+/// let sb = Suggestionbox::new("http://localhost:8080");
+/// let local = Model::from_file(&mut File::open("articles.json").unwrap()).unwrap();
+/// let remote = sb.get_model("articles").unwrap();
+///
+/// let diff = ModelDiff::between(&remote, &local);
+/// if !diff.is_empty() {
+///     println!("the model on the box differs from articles.json: {:?}", diff.choices);
+/// }
+/// let updated = diff.apply(&remote);
+/// assert_eq!(updated.choices.len(), local.choices.len());
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ModelDiff {
+    /// Choice changes, with removals first, then feature replacements, then additions
+    pub choices: Vec<ChoiceChange>,
+    /// Whether the local model has a different name
+    pub name_changed: bool,
+    /// Whether the local model sets options that differ from the box's. A local model
+    /// without options leaves the box's options as they are.
+    pub options_changed: bool,
+}
+
+impl ModelDiff {
+    /// Computes the changes that turn `remote`, as returned by `get_model`, into `local`.
+    /// Choices are matched by ID, and features are compared in order.
+    pub fn between(remote: &Model, local: &Model) -> ModelDiff {
+        let find = |model: &Model, id: &str| -> Option<Choice> {
+            model.choices.iter().find(|choice| choice.id == id).cloned()
+        };
+
+        let mut removed = Vec::new();
+        let mut replaced = Vec::new();
+        for choice in &remote.choices {
+            match find(local, &choice.id) {
+                None => removed.push(ChoiceChange::Remove(choice.id.clone())),
+                Some(wanted) => {
+                    if wanted.features != choice.features {
+                        replaced.push(ChoiceChange::ReplaceFeatures {
+                            id: wanted.id,
+                            features: wanted.features,
+                        });
+                    }
+                }
+            }
+        }
+        let added = local
+            .choices
+            .iter()
+            .filter(|choice| find(remote, &choice.id).is_none())
+            .map(|choice| ChoiceChange::Add(choice.clone()));
+
+        let mut choices = removed;
+        choices.extend(replaced);
+        choices.extend(added);
+        ModelDiff {
+            choices,
+            name_changed: local.name != remote.name,
+            options_changed: local.options.is_some() && local.options != remote.options,
+        }
+    }
+
+    /// Returns a copy of `model` with the choice changes applied, in order. Its name and
+    /// options are left as they are.
+    pub fn apply(&self, model: &Model) -> Model {
+        let mut model = model.clone();
+        for change in &self.choices {
+            match *change {
+                ChoiceChange::Add(ref choice) => model.choices.push(choice.clone()),
+                ChoiceChange::Remove(ref id) => model.choices.retain(|choice| choice.id != *id),
+                ChoiceChange::ReplaceFeatures { ref id, ref features } => {
+                    for choice in model.choices.iter_mut().filter(|choice| choice.id == *id) {
+                        choice.features = features.clone();
+                    }
+                }
+            }
+        }
+        model
+    }
+
+    /// Indicates whether the two models are the same
+    pub fn is_empty(&self) -> bool {
+        self.choices.is_empty() && !self.name_changed && !self.options_changed
+    }
+}
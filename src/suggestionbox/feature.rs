@@ -0,0 +1,270 @@
+use serde::ser::Error as SerError;
+use serde::{Serialize, Serializer};
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fmt;
+
+/// Tells suggestionbox how to treat the feature value when making predictions
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureType {
+    /// Indicates a numerical feature
+    #[serde(rename = "number")]
+    Number,
+    /// Indicates a textual feature that will be tokenized. Use `Keyword` if you don't want
+    /// tokenization.
+    #[serde(rename = "text")]
+    Text,
+    /// Indicates a non-tokenized textual feature. A `list` can be used to supply multiple
+    /// keywords as a feature value
+    #[serde(rename = "keyword")]
+    Keyword,
+    /// Indicates a list of non-tokenized keywords.
+    #[serde(rename = "list")]
+    List,
+    /// Indicates a feature value that is a URL
+    #[serde(rename = "image_url")]
+    ImageURL,
+    /// Indicates a feature value that is a binary image encoded with Base64
+    #[serde(rename = "image_base64")]
+    ImageBase64,
+}
+
+/// The value of a feature, which also determines how suggestionbox treats it
+#[derive(Debug, Clone, PartialEq)]
+pub enum FeatureValue {
+    /// A numerical value
+    Number(f64),
+    /// Text that will be tokenized
+    Text(String),
+    /// Text that will not be tokenized
+    Keyword(String),
+    /// A list of keywords.
+    ///
+    /// suggestionbox receives a list as a single comma separated string and splits it
+    /// on the commas, trimming whitespace from each item. It has no way to escape a comma,
+    /// so an item that is empty, contains a comma or starts or ends with whitespace
+    /// can't be sent intact. Such lists are rejected rather than sent corrupted: they
+    /// fail to serialize, and `Model::validate` and `Choice::validate` report them.
+    List(Vec<String>),
+    /// The URL of an image
+    ImageUrl(String),
+    /// A base64 encoded image
+    ImageBase64(String),
+}
+
+impl FeatureValue {
+    /// The type suggestionbox is told the value has
+    pub fn feature_type(&self) -> FeatureType {
+        match *self {
+            FeatureValue::Number(_) => FeatureType::Number,
+            FeatureValue::Text(_) => FeatureType::Text,
+            FeatureValue::Keyword(_) => FeatureType::Keyword,
+            FeatureValue::List(_) => FeatureType::List,
+            FeatureValue::ImageUrl(_) => FeatureType::ImageURL,
+            FeatureValue::ImageBase64(_) => FeatureType::ImageBase64,
+        }
+    }
+
+    /// The value of a numerical feature
+    pub fn as_number(&self) -> Option<f64> {
+        match *self {
+            FeatureValue::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// The value of a text, keyword or image feature
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            FeatureValue::Text(ref s)
+            | FeatureValue::Keyword(ref s)
+            | FeatureValue::ImageUrl(ref s)
+            | FeatureValue::ImageBase64(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The keywords of a list feature
+    pub fn as_list(&self) -> Option<&[String]> {
+        match *self {
+            FeatureValue::List(ref items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+/// A feature is used to describe an input or a choice. For example, age:28 or location:"London"
+///
+/// Features are serialized the way suggestionbox expects them, with numbers as strings and
+/// lists as comma separated strings. A list with an item that can't be represented that
+/// way, because it is empty, contains a comma or is padded with whitespace, fails to
+/// serialize; `Model::validate` and `Choice::validate` report such lists up front.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(try_from = "WireFeature<'static>")]
+pub struct Feature {
+    /// The feature's key
+    pub key: String,
+    /// The value of the feature
+    pub value: FeatureValue,
+}
+
+impl Feature {
+    /// The type of this feature
+    pub fn feature_type(&self) -> FeatureType {
+        self.value.feature_type()
+    }
+
+    /// Shortcut for producing a new text feature
+    pub fn text(key: &str, text: &str) -> Feature {
+        Feature::new(key, FeatureValue::Text(text.to_owned()))
+    }
+
+    /// Shortcut for production a numerical feature
+    pub fn number(key: &str, number: f64) -> Feature {
+        Feature::new(key, FeatureValue::Number(number))
+    }
+
+    /// Shortcut for producing a keyword feature
+    pub fn keyword(key: &str, keyword: &str) -> Feature {
+        Feature::new(key, FeatureValue::Keyword(keyword.to_owned()))
+    }
+
+    /// Shortcut for producing a keyword list feature
+    pub fn list(key: &str, list: Vec<&str>) -> Feature {
+        let items = list.into_iter().map(str::to_owned).collect();
+        Feature::new(key, FeatureValue::List(items))
+    }
+
+    /// Shortcut for producing an image URL feature
+    pub fn image_url(key: &str, url: &str) -> Feature {
+        Feature::new(key, FeatureValue::ImageUrl(url.to_owned()))
+    }
+
+    /// Shortcut for producing a base64-encoded image feature
+    pub fn image_base64(key: &str, data: &str) -> Feature {
+        Feature::new(key, FeatureValue::ImageBase64(data.to_owned()))
+    }
+
+    fn new(key: &str, value: FeatureValue) -> Feature {
+        Feature {
+            key: key.to_owned(),
+            value,
+        }
+    }
+}
+
+/// A feature as it appears in suggestionbox's JSON. Serializing borrows from the feature,
+/// while deserializing always produces owned strings.
+#[derive(Serialize, Deserialize)]
+struct WireFeature<'a> {
+    key: Cow<'a, str>,
+    #[serde(rename = "type")]
+    feature_type: FeatureType,
+    value: WireValue<'a>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum WireValue<'a> {
+    String(Cow<'a, str>),
+    Number(f64),
+}
+
+impl Serialize for Feature {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        WireFeature::try_from(self)
+            .map_err(S::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl<'a> TryFrom<&'a Feature> for WireFeature<'a> {
+    type Error = InvalidFeature;
+
+    fn try_from(feature: &'a Feature) -> Result<WireFeature<'a>, InvalidFeature> {
+        let feature_type = feature.feature_type();
+        let value = match feature.value {
+            FeatureValue::Number(n) => Cow::Owned(n.to_string()),
+            FeatureValue::List(ref items) => {
+                if !items.iter().all(|item| joinable(item)) {
+                    return Err(InvalidFeature {
+                        key: feature.key.clone(),
+                        feature_type,
+                    });
+                }
+                Cow::Owned(items.join(","))
+            }
+            FeatureValue::Text(ref s)
+            | FeatureValue::Keyword(ref s)
+            | FeatureValue::ImageUrl(ref s)
+            | FeatureValue::ImageBase64(ref s) => Cow::Borrowed(s.as_str()),
+        };
+        Ok(WireFeature {
+            key: Cow::Borrowed(&feature.key),
+            feature_type,
+            value: WireValue::String(value),
+        })
+    }
+}
+
+impl<'a> TryFrom<WireFeature<'a>> for Feature {
+    type Error = InvalidFeature;
+
+    fn try_from(wire: WireFeature<'a>) -> Result<Feature, InvalidFeature> {
+        let number = |s: &str| s.trim().parse().ok().map(FeatureValue::Number);
+        let value = match (wire.feature_type, wire.value) {
+            (FeatureType::Number, WireValue::Number(n)) => Some(FeatureValue::Number(n)),
+            (FeatureType::Number, WireValue::String(s)) => number(&s),
+            (FeatureType::List, WireValue::String(s)) => Some(FeatureValue::List(split_list(&s))),
+            (FeatureType::Text, WireValue::String(s)) => {
+                Some(FeatureValue::Text(s.into_owned()))
+            }
+            (FeatureType::Keyword, WireValue::String(s)) => {
+                Some(FeatureValue::Keyword(s.into_owned()))
+            }
+            (FeatureType::ImageURL, WireValue::String(s)) => {
+                Some(FeatureValue::ImageUrl(s.into_owned()))
+            }
+            (FeatureType::ImageBase64, WireValue::String(s)) => {
+                Some(FeatureValue::ImageBase64(s.into_owned()))
+            }
+            _ => None,
+        };
+        match value {
+            Some(value) => Ok(Feature {
+                key: wire.key.into_owned(),
+                value,
+            }),
+            None => Err(InvalidFeature {
+                key: wire.key.into_owned(),
+                feature_type: wire.feature_type,
+            }),
+        }
+    }
+}
+
+/// The error produced when a feature's value doesn't match its type
+#[derive(Debug)]
+struct InvalidFeature {
+    key: String,
+    feature_type: FeatureType,
+}
+
+impl fmt::Display for InvalidFeature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "feature {} has a value that is not valid for type {:?}", self.key,
+               self.feature_type)
+    }
+}
+
+/// Indicates whether a list item survives being joined with commas and split again
+pub(crate) fn joinable(item: &str) -> bool {
+    !item.is_empty() && !item.contains(',') && item.trim() == item
+}
+
+fn split_list(list: &str) -> Vec<String> {
+    if list.trim().is_empty() {
+        return Vec::new();
+    }
+    list.split(',').map(|item| item.trim().to_owned()).collect()
+}
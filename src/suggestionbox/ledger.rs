@@ -0,0 +1,360 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, SystemTime};
+
+use super::{Prediction, PredictionRequest, PredictionResponse, SuggestionClient,
+            DEFAULT_REWARD_EXPIRATION_SECONDS};
+use {Error, Kind, Result};
+
+/// A prediction made by suggestionbox, as recorded by a `RewardLedger`
+#[derive(Debug, Clone)]
+pub struct PredictionRecord {
+    /// The model that made the prediction
+    pub model_id: String,
+    /// When the prediction was made
+    pub predicted_at: SystemTime,
+    /// When the prediction's rewards expire, if the model's reward expiration is known
+    pub expires_at: Option<SystemTime>,
+    /// The predicted choices, each with its own reward ID
+    pub choices: Vec<Prediction>,
+    /// The reward posted for the prediction, if any
+    pub reward: Option<RewardRecord>,
+}
+
+impl PredictionRecord {
+    /// Indicates whether the prediction can no longer be rewarded at `now`
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// A reward posted through a `RewardLedger`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RewardRecord {
+    /// The reward ID that was posted
+    pub reward_id: String,
+    /// The choice the reward ID belonged to
+    pub choice_id: String,
+    /// The weight of the reward
+    pub weight: f64,
+    /// When the reward was posted
+    pub rewarded_at: SystemTime,
+}
+
+/// How many of the recorded predictions have been rewarded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Coverage {
+    /// The number of predictions recorded
+    pub predictions: usize,
+    /// The number of predictions that were rewarded
+    pub rewarded: usize,
+    /// The number of unrewarded predictions that can still be rewarded
+    pub pending: usize,
+    /// The number of unrewarded predictions whose rewards have expired
+    pub expired: usize,
+}
+
+impl Coverage {
+    /// The proportion of predictions that were rewarded, or 0 if there were none
+    pub fn ratio(&self) -> f64 {
+        if self.predictions == 0 {
+            0.0
+        } else {
+            self.rewarded as f64 / self.predictions as f64
+        }
+    }
+}
+
+/// Keeps track of the reward IDs handed out by predictions, so that rewards can be posted
+/// without remembering which model and prediction they came from, and so that predictions
+/// that were never rewarded can be reported on.
+///
+/// Rewards posted through the ledger after their prediction's reward expiration has
+/// passed are refused with `Kind::RewardExpired`, rather than sent to a box that would
+/// ignore them. Each prediction can be rewarded once; further rewards for it are refused
+/// with `Kind::AlreadyRewarded`. Both still apply once the prediction has been drained.
+/// # Examples
+/// ```no_run
+/// use machinebox::suggestionbox::{PredictionRequest, RewardLedger, Suggestionbox};
+///
+/// let sb = Suggestionbox::new("http://localhost:8080");
+/// let mut ledger = RewardLedger::new();
+///
+/// let request = PredictionRequest { inputs: Vec::new() };
+/// let prediction = ledger.predict(&sb, "articles", &request).unwrap();
+/// // ...later, when the user clicks the second article
+/// ledger.reward(&sb, "articles", &prediction.choices[1].reward_id, 1.0).unwrap();
+///
+/// let coverage = ledger.coverage();
+/// println!("{:.0}% of predictions were rewarded", coverage.ratio() * 100.0);
+/// ```
+pub struct RewardLedger {
+    clock: Box<dyn Fn() -> SystemTime + Send + Sync>,
+    expirations: HashMap<String, Option<Duration>>,
+    next_record: u64,
+    records: BTreeMap<u64, PredictionRecord>,
+    by_reward: HashMap<String, u64>,
+    settled: BTreeMap<u64, Settled>,
+    drained_coverage: HashMap<String, Coverage>,
+    grace: Duration,
+}
+
+/// How long the reward IDs of a drained prediction are remembered past its expiry, unless
+/// a different period is chosen with `set_grace_period`
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+/// What the ledger remembers about a drained prediction, so that rewards for it can still
+/// be refused. Its reward IDs keep pointing at it in `by_reward`.
+#[derive(Debug, Clone)]
+struct Settled {
+    model_id: String,
+    rewarded: bool,
+    /// When the prediction's rewards expire, or when it was drained if that isn't known
+    forget_after: SystemTime,
+}
+
+impl Default for RewardLedger {
+    fn default() -> Self {
+        RewardLedger::new()
+    }
+}
+
+impl RewardLedger {
+    /// Creates an empty ledger that uses the system clock
+    pub fn new() -> RewardLedger {
+        RewardLedger::with_clock(SystemTime::now)
+    }
+
+    /// Creates an empty ledger that tells the time with `clock`, which allows expiry to be
+    /// tested without waiting
+    pub fn with_clock<F>(clock: F) -> RewardLedger
+    where
+        F: Fn() -> SystemTime + Send + Sync + 'static,
+    {
+        RewardLedger {
+            clock: Box::new(clock),
+            expirations: HashMap::new(),
+            next_record: 0,
+            records: BTreeMap::new(),
+            by_reward: HashMap::new(),
+            settled: BTreeMap::new(),
+            drained_coverage: HashMap::new(),
+            grace: DEFAULT_GRACE_PERIOD,
+        }
+    }
+
+    /// Sets how long the predictions of a model can be rewarded for. When it isn't set,
+    /// `predict` looks it up from the model's `reward_expiration_seconds`. A model that
+    /// leaves it at 0, or has no options, uses the box's default of
+    /// `DEFAULT_REWARD_EXPIRATION_SECONDS`.
+    pub fn set_expiration(&mut self, model_id: &str, expiration: Duration) {
+        self.expirations.insert(model_id.to_owned(), Some(expiration));
+    }
+
+    /// Asks for a prediction, as `Suggestionbox::predict` would, and records it
+    pub fn predict<C: SuggestionClient>(&mut self, client: &C, model_id: &str,
+                                        request: &PredictionRequest) -> Result<PredictionResponse> {
+        if !self.expirations.contains_key(model_id) {
+            let model = client.get_model(model_id)?;
+            let expiration = match model.options.map_or(0, |o| o.reward_expiration_seconds) {
+                0 => DEFAULT_REWARD_EXPIRATION_SECONDS,
+                secs => secs,
+            };
+            self.expirations
+                .insert(model_id.to_owned(), Some(Duration::from_secs(expiration)));
+        }
+        let response = client.predict(model_id, request)?;
+        self.record(model_id, &response);
+        Ok(response)
+    }
+
+    /// Records a prediction that was made without going through the ledger. Its rewards
+    /// only expire if the model's reward expiration is already known to the ledger.
+    pub fn record(&mut self, model_id: &str, response: &PredictionResponse) {
+        let predicted_at = (self.clock)();
+        let expiration = self.expirations.get(model_id).cloned().unwrap_or(None);
+        let id = self.next_record;
+        self.next_record += 1;
+        for choice in &response.choices {
+            self.by_reward.insert(choice.reward_id.clone(), id);
+        }
+        self.records.insert(id, PredictionRecord {
+            model_id: model_id.to_owned(),
+            predicted_at,
+            expires_at: expiration.map(|expiration| predicted_at + expiration),
+            choices: response.choices.clone(),
+            reward: None,
+        });
+    }
+
+    /// Posts a reward, as `Suggestionbox::reward` would, and records it against the
+    /// prediction it came from. Fails without contacting the box with
+    /// `Kind::RewardModelMismatch` if the reward ID was handed out by a different model,
+    /// with `Kind::RewardExpired` if the prediction's rewards have expired, and with
+    /// `Kind::AlreadyRewarded` if the prediction has already been rewarded. Reward IDs
+    /// of drained predictions are refused in the same way. Reward IDs the ledger has never
+    /// seen are passed on to the box unchecked.
+    pub fn reward<C: SuggestionClient>(&mut self, client: &C, model_id: &str, reward_id: &str,
+                                       weight: f64) -> Result<()> {
+        let now = (self.clock)();
+        let record = self.by_reward.get(reward_id).cloned();
+        let refusal = match record.and_then(|id| self.records.get(&id)) {
+            Some(record) => {
+                let expired = record.reward.is_none() && record.is_expired(now);
+                Some((&record.model_id, record.reward.is_some(), expired))
+            }
+            None => record
+                .and_then(|id| self.settled.get(&id))
+                .map(|settled| (&settled.model_id, settled.rewarded, !settled.rewarded)),
+        };
+        if let Some((expected, rewarded, expired)) = refusal {
+            if *expected != model_id {
+                return Err(Error::from(Kind::RewardModelMismatch {
+                    model_id: model_id.to_owned(),
+                    reward_id: reward_id.to_owned(),
+                    expected: expected.clone(),
+                }));
+            }
+            if rewarded {
+                return Err(Error::from(Kind::AlreadyRewarded {
+                    model_id: model_id.to_owned(),
+                    reward_id: reward_id.to_owned(),
+                }));
+            }
+            if expired {
+                return Err(Error::from(Kind::RewardExpired {
+                    model_id: model_id.to_owned(),
+                    reward_id: reward_id.to_owned(),
+                }));
+            }
+        }
+
+        client.reward(model_id, reward_id, weight)?;
+
+        if let Some(record) = record.and_then(|id| self.records.get_mut(&id)) {
+            let choice_id = record
+                .choices
+                .iter()
+                .find(|choice| choice.reward_id == reward_id)
+                .map(|choice| choice.id.clone())
+                .unwrap_or_default();
+            record.reward = Some(RewardRecord {
+                reward_id: reward_id.to_owned(),
+                choice_id,
+                weight,
+                rewarded_at: now,
+            });
+        }
+        Ok(())
+    }
+
+    /// The prediction that handed out the given reward ID
+    pub fn prediction(&self, reward_id: &str) -> Option<&PredictionRecord> {
+        self.by_reward.get(reward_id).and_then(|id| self.records.get(id))
+    }
+
+    /// All recorded predictions, oldest first
+    pub fn predictions(&self) -> Vec<&PredictionRecord> {
+        self.records.values().collect()
+    }
+
+    /// The recorded predictions that haven't been rewarded, oldest first, whether or not
+    /// they have expired
+    pub fn unrewarded(&self) -> Vec<&PredictionRecord> {
+        self.records.values().filter(|record| record.reward.is_none()).collect()
+    }
+
+    /// Reports how many of the recorded predictions have been rewarded, including those
+    /// that have since been drained
+    pub fn coverage(&self) -> Coverage {
+        self.coverage_of(|_| true)
+    }
+
+    /// Reports how many of the recorded predictions of one model have been rewarded,
+    /// including those that have since been drained
+    pub fn model_coverage(&self, model_id: &str) -> Coverage {
+        self.coverage_of(|id| id == model_id)
+    }
+
+    /// Sets how long the reward IDs of a drained prediction are remembered once its
+    /// rewards have expired, or once it was drained if its expiry isn't known. Rewards for
+    /// a prediction the ledger has forgotten are passed on to the box unchecked.
+    pub fn set_grace_period(&mut self, grace: Duration) {
+        self.grace = grace;
+    }
+
+    /// Removes and returns the predictions that can no longer be rewarded, either because
+    /// they have been or because they have expired. Predictions that might still be
+    /// rewarded are kept. The ledger goes on remembering the reward IDs of drained
+    /// predictions for the grace period, so that rewards for them are still refused, and
+    /// their outcomes go on counting towards `coverage`.
+    pub fn drain_settled(&mut self) -> Vec<PredictionRecord> {
+        let now = (self.clock)();
+        let settled: Vec<u64> = self
+            .records
+            .iter()
+            .filter(|&(_, record)| record.reward.is_some() || record.is_expired(now))
+            .map(|(&id, _)| id)
+            .collect();
+
+        let mut drained = Vec::with_capacity(settled.len());
+        for id in settled {
+            if let Some(record) = self.records.remove(&id) {
+                let totals = self.drained_coverage.entry(record.model_id.clone()).or_default();
+                totals.predictions += 1;
+                if record.reward.is_some() {
+                    totals.rewarded += 1;
+                } else {
+                    totals.expired += 1;
+                }
+                self.settled.insert(id, Settled {
+                    model_id: record.model_id.clone(),
+                    rewarded: record.reward.is_some(),
+                    forget_after: record.expires_at.unwrap_or(now),
+                });
+                drained.push(record);
+            }
+        }
+        self.forget_settled(now);
+        drained
+    }
+
+    /// Forgets the drained predictions whose grace period has passed, along with their
+    /// reward IDs
+    fn forget_settled(&mut self, now: SystemTime) {
+        let grace = self.grace;
+        let before = self.settled.len();
+        self.settled.retain(|_, settled| settled.forget_after + grace > now);
+        if self.settled.len() != before {
+            let (records, settled) = (&self.records, &self.settled);
+            self.by_reward
+                .retain(|_, id| records.contains_key(id) || settled.contains_key(id));
+        }
+    }
+
+    /// The number of reward IDs the ledger is keeping track of, drained or not
+    #[cfg(test)]
+    pub(crate) fn tracked_reward_ids(&self) -> usize {
+        self.by_reward.len()
+    }
+
+    fn coverage_of<F: Fn(&str) -> bool>(&self, include: F) -> Coverage {
+        let now = (self.clock)();
+        let mut coverage = Coverage::default();
+        for (_, drained) in self.drained_coverage.iter().filter(|&(id, _)| include(id)) {
+            coverage.predictions += drained.predictions;
+            coverage.rewarded += drained.rewarded;
+            coverage.expired += drained.expired;
+        }
+        for record in self.records.values().filter(|record| include(&record.model_id)) {
+            coverage.predictions += 1;
+            if record.reward.is_some() {
+                coverage.rewarded += 1;
+            } else if record.is_expired(now) {
+                coverage.expired += 1;
+            } else {
+                coverage.pending += 1;
+            }
+        }
+        coverage
+    }
+}
@@ -4,72 +4,85 @@
 //! and to train them by rewarding the predictions.
 //!
 //! For more information, see the [suggestionbox docs](https://machinebox.io/docs/suggestionbox)
-use super::BoxClient;
+use super::{BoxClient, Connection, Error, Kind};
 use super::Result;
-use reqwest;
-use reqwest::StatusCode;
-use serde_json;
-use Error;
-use Kind;
-
-pub use self::types::{Choice, Feature, FeatureType, Model, ModelBuilder, ModelOptions, ModelStats};
-pub use self::types::{Prediction, PredictionRequest, PredictionResponse, Reward};
-use std::io::Write;
+
+pub use self::diff::{ChoiceChange, ModelDiff};
+pub use self::feature::{Feature, FeatureType, FeatureValue};
+pub use self::ledger::{Coverage, PredictionRecord, RewardLedger, RewardRecord,
+                       DEFAULT_GRACE_PERIOD};
+pub use self::types::{Choice, Model, ModelBuilder, ModelOptions, ModelStats};
+pub use self::simulator::Simulator;
+pub use self::types::{Prediction, PredictionRequest, PredictionResponse, Reward, ValidationError};
+use std::io::{Read, Write};
 use std::collections::HashMap;
-use utils::{post_json, delete_with_response, get_json};
+use utils::{post_json, post_form_vars, post_multipart_file_field,
+            post_multipart_reader_field, delete_with_response, get_json, get_stream, parse, Retry};
 use utils::RawBoxResponse;
 use suggestionbox::types::{ModelList, PredictionResponseFull};
 
+/// The number of seconds suggestionbox accepts rewards for when a model's
+/// `reward_expiration_seconds` is 0 or its options are left out
+pub const DEFAULT_REWARD_EXPIRATION_SECONDS: u64 = 30;
+
 /// The client for the `suggestionbox` machine box.
 pub struct Suggestionbox {
-    url: String,
+    conn: Connection,
 }
 
 impl Suggestionbox {
     /// Creates a new suggestionbox client
     pub fn new(url: &str) -> Suggestionbox {
-        Suggestionbox { url: url.to_owned() }
+        Suggestionbox::with_connection(Connection::new(url))
+    }
+
+    /// Creates a new suggestionbox client that makes all of its requests through the
+    /// supplied connection
+    pub fn with_connection(conn: Connection) -> Suggestionbox {
+        Suggestionbox { conn }
     }
 
     /// Creates a new model and returns a copy of the model as seen by the suggestion
-    /// box, including the options used in model generation.
+    /// box, including the options used in model generation. The model is validated
+    /// first, and is not sent if it fails with `Kind::InvalidModel`.
     pub fn create_model(&self, model: &Model) -> Result<Model> {
+        model.validate().map_err(|errors| Error::from(Kind::InvalidModel(errors)))?;
         let url = format!("{}/suggestionbox/models", self.url());
-        let raw = post_json(&url,model)?;
-        let model:Model = serde_json::from_str(&raw)?;
+        let raw = post_json(&self.conn, &url, model, Retry::NonIdempotent)?;
+        let model: Model = parse(&raw)?;
         Ok(model)
     }
 
     /// Deletes a model from the box. If the model doesn't exist, it will return
-    /// an error of type `Machinebox` indicating an HTTP 404.
+    /// an error for which `is_not_found()` is true.
     pub fn delete_model(&self, id: &str) -> Result<()> {
         let url = format!("{}/suggestionbox/models/{}", self.url(), id);
-        let raw = delete_with_response(&url)?;
-        let raw_response: RawBoxResponse = serde_json::from_str(&raw)?;
+        let raw = delete_with_response(&self.conn, &url)?;
+        let raw_response: RawBoxResponse = parse(&raw)?;
         raw_response.into()
     }
 
     /// Retrieves a single model from the box
     pub fn get_model(&self, id: &str) -> Result<Model> {
         let url = format!("{}/suggestionbox/models/{}", self.url(), id);
-        let raw = get_json(&url)?;
-        let model: Model = serde_json::from_str(&raw)?;
+        let raw = get_json(&self.conn, &url)?;
+        let model: Model = parse(&raw)?;
         Ok(model)
     }
 
     /// Lists all of the models currently managed by the suggestion box
     pub fn list_models(&self) -> Result<Vec<Model>> {
         let url = format!("{}/suggestionbox/models", self.url());
-        let raw = get_json(&url)?;
-        let models:ModelList = serde_json::from_str(&raw)?;
+        let raw = get_json(&self.conn, &url)?;
+        let models: ModelList = parse(&raw)?;
         models.into()
     }
 
     /// Obtains statistics about the given model
     pub fn get_model_stats(&self, id: &str) -> Result<ModelStats> {
         let url = format!("{}/suggestionbox/models/{}/stats", self.url(), id);
-        let raw = get_json(&url)?;
-        let stats: ModelStats = serde_json::from_str(&raw)?;
+        let raw = get_json(&self.conn, &url)?;
+        let stats: ModelStats = parse(&raw)?;
         Ok(stats)
     }
 
@@ -83,8 +96,8 @@ impl Suggestionbox {
         request: &PredictionRequest,
     ) -> Result<PredictionResponse> {
         let url = format!("{}/suggestionbox/models/{}/predict", self.url(), model_id);
-        let raw = post_json(&url, request)?;
-        let predict_response: PredictionResponseFull = serde_json::from_str(&raw)?;
+        let raw = post_json(&self.conn, &url, request, Retry::Idempotent)?;
+        let predict_response: PredictionResponseFull = parse(&raw)?;
         predict_response.into()
     }
 
@@ -97,8 +110,8 @@ impl Suggestionbox {
             value: weight,
         };
         let url = format!("{}/suggestionbox/models/{}/rewards", self.url(), model_id);
-        let raw = post_json(&url, &reward)?;
-        let raw_response: RawBoxResponse = serde_json::from_str(&raw)?;
+        let raw = post_json(&self.conn, &url, &reward, Retry::NonIdempotent)?;
+        let raw_response: RawBoxResponse = parse(&raw)?;
         raw_response.into()
     }
 
@@ -111,40 +124,29 @@ impl Suggestionbox {
         W: Write,
     {
         let url = format!("{}/suggestionbox/state/{}", self.url(), model_id);
-        let mut resp = reqwest::get(&url)?;
-        if resp.status() != StatusCode::Ok {
-            let raw = resp.text()?;
-            Err(Error {
-                kind: Kind::Machinebox(format!("HTTP {}: {}", resp.status(), raw)),
-            })
-        } else {
-            let bytecount = resp.copy_to(buf)?;
-            Ok(bytecount)
-        }
+        let mut resp = get_stream(&self.conn, &url)?;
+        let bytecount = resp.copy_to(buf)?;
+        Ok(bytecount)
     }
 
     /// Submits the state file indicated by the `source_path` parameter to the suggestion box
     /// and returns the model originally contained in the state file
     pub fn post_state(&self, source_path: &str) -> Result<Model> {
         let url = format!("{}/suggestionbox/state", self.url());
-        let form = reqwest::multipart::Form::new().file("state", source_path)?;
-        let client = reqwest::Client::new();
-        match client.post(&url).multipart(form).send() {
-            Ok(mut response) => {
-                let raw = response.text()?;
-                if response.status() != StatusCode::Ok {
-                    Err(Error {
-                        kind: Kind::Machinebox(format!("HTTP {}: {}", response.status(), raw)),
-                    })
-                } else {
-                    let model: Model = serde_json::from_str(&raw)?;
-                    Ok(model)
-                }
-            }
-            Err(e) => Err(Error {
-                kind: Kind::Reqwest(e),
-            }),
-        }
+        let raw = post_multipart_file_field(&self.conn, &url, "state", source_path, Retry::Never)?;
+        let model: Model = parse(&raw)?;
+        Ok(model)
+    }
+
+    /// Submits the state file read from `reader` to the suggestion box and returns the
+    /// model originally contained in it. The state is streamed to the box as it is read, so
+    /// it can come straight from memory, a network stream or a decompressor without being
+    /// staged to disk. As the reader can only be consumed once, the upload is never retried.
+    pub fn post_state_reader<R: Read + Send + 'static>(&self, reader: R) -> Result<Model> {
+        let url = format!("{}/suggestionbox/state", self.url());
+        let raw = post_multipart_reader_field(&self.conn, &url, "state", reader)?;
+        let model: Model = parse(&raw)?;
+        Ok(model)
     }
 
     /// Submits a URL to the suggestion box. The suggestion box will download the state
@@ -153,32 +155,72 @@ impl Suggestionbox {
         let url = format!("{}/suggestionbox/state", self.url());
         let mut params = HashMap::new();
         params.insert("url", state_url);
-        let client = reqwest::Client::new();
-        match client.post(&url).form(&params).send() {
-            Ok(mut response) => {
-                let raw = response.text()?;
-                if response.status() != StatusCode::Ok {
-                    Err(Error {
-                        kind: Kind::Machinebox(format!("HTTP {}: {}", response.status(), raw)),
-                    })
-                } else {
-                    let model: Model = serde_json::from_str(&raw)?;
-                    Ok(model)
-                }
-            }
-            Err(e) => Err(Error {
-                kind: Kind::Reqwest(e),
-            }),
-        }
+        let raw = post_form_vars(&self.conn, &url, &params, Retry::Never)?;
+        let model: Model = parse(&raw)?;
+        Ok(model)
+    }
+}
+
+/// The model, prediction and reward operations of suggestionbox. This is implemented both
+/// by the `Suggestionbox` client and by the offline `Simulator`, so that code written
+/// against it can be tested without a live box.
+pub trait SuggestionClient {
+    /// Creates a new model and returns a copy of the model as seen by the box
+    fn create_model(&self, model: &Model) -> Result<Model>;
+    /// Deletes a model
+    fn delete_model(&self, id: &str) -> Result<()>;
+    /// Retrieves a single model
+    fn get_model(&self, id: &str) -> Result<Model>;
+    /// Lists all of the models
+    fn list_models(&self) -> Result<Vec<Model>>;
+    /// Obtains statistics about the given model
+    fn get_model_stats(&self, id: &str) -> Result<ModelStats>;
+    /// Makes a prediction based upon the supplied list of features
+    fn predict(&self, model_id: &str, request: &PredictionRequest) -> Result<PredictionResponse>;
+    /// Tells the box about a successful prediction
+    fn reward(&self, model_id: &str, reward_id: &str, weight: f64) -> Result<()>;
+}
+
+impl SuggestionClient for Suggestionbox {
+    fn create_model(&self, model: &Model) -> Result<Model> {
+        Suggestionbox::create_model(self, model)
+    }
+
+    fn delete_model(&self, id: &str) -> Result<()> {
+        Suggestionbox::delete_model(self, id)
+    }
+
+    fn get_model(&self, id: &str) -> Result<Model> {
+        Suggestionbox::get_model(self, id)
+    }
+
+    fn list_models(&self) -> Result<Vec<Model>> {
+        Suggestionbox::list_models(self)
+    }
+
+    fn get_model_stats(&self, id: &str) -> Result<ModelStats> {
+        Suggestionbox::get_model_stats(self, id)
+    }
+
+    fn predict(&self, model_id: &str, request: &PredictionRequest) -> Result<PredictionResponse> {
+        Suggestionbox::predict(self, model_id, request)
+    }
+
+    fn reward(&self, model_id: &str, reward_id: &str, weight: f64) -> Result<()> {
+        Suggestionbox::reward(self, model_id, reward_id, weight)
     }
 }
 
 impl BoxClient for Suggestionbox {
-    fn url(&self) -> &str {
-        &self.url
+    fn connection(&self) -> &Connection {
+        &self.conn
     }
 }
 
+mod diff;
+mod feature;
+mod ledger;
+mod simulator;
 mod types;
 
 #[cfg(test)]
@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{Model, ModelOptions, DEFAULT_REWARD_EXPIRATION_SECONDS, ModelStats, Prediction, PredictionRequest, PredictionResponse,
+            SuggestionClient};
+use {Error, Kind, Result};
+
+/// How often a model explores when it has no options of its own
+const DEFAULT_EPSILON: f64 = 0.3;
+
+/// An in-process stand-in for suggestionbox, for testing code that makes predictions and
+/// posts rewards without a live box.
+///
+/// Each model learns a simple multi-armed bandit over its choices, ignoring the features of
+/// the request. Models whose options set `softmax_lambda` pick choices with a softmax over
+/// the average reward of each choice; all others explore with probability `epsilon`, and
+/// otherwise pick the choice with the best average reward. Rewards must be posted within
+/// `reward_expiration_seconds` of the prediction, or within
+/// `DEFAULT_REWARD_EXPIRATION_SECONDS` if it is 0, as
+/// measured by a simulated clock that only moves when `advance` is called. Given the same
+/// seed, the simulator makes the same predictions every time.
+/// # Examples
+/// ```
+/// use machinebox::suggestionbox::{Feature, ModelBuilder, ModelOptions, PredictionRequest,
+///                                 Simulator, SuggestionClient};
+///
+/// let sim = Simulator::with_seed(42);
+/// let model = ModelBuilder::new()
+///     .id("articles")
+///     .choice("article1", vec![Feature::text("title", "Machine Box releases new product")])
+///     .choice("article2", vec![Feature::text("title", "The beatles reunite")])
+///     // Never explore, so that the best choice always comes first
+///     .options(ModelOptions {
+///         reward_expiration_seconds: 60,
+///         epsilon: 0.0,
+///         softmax_lambda: 0.0,
+///         ngrams: 1,
+///         skipgrams: 0,
+///     })
+///     .finish()
+///     .unwrap();
+/// sim.create_model(&model).unwrap();
+///
+/// let request = PredictionRequest { inputs: vec![Feature::number("age", 28.0)] };
+/// for _ in 0..100 {
+///     let prediction = sim.predict("articles", &request).unwrap();
+///     let beatles = prediction.choices.iter().find(|c| c.id == "article2").unwrap();
+///     sim.reward("articles", &beatles.reward_id, 1.0).unwrap();
+/// }
+/// assert_eq!(sim.predict("articles", &request).unwrap().choices[0].id, "article2");
+/// ```
+pub struct Simulator {
+    state: Mutex<State>,
+}
+
+struct State {
+    rng: Rng,
+    clock: Duration,
+    next_model: u64,
+    next_reward: u64,
+    models: Vec<SimulatedModel>,
+    pending: HashMap<String, PendingReward>,
+}
+
+struct SimulatedModel {
+    model: Model,
+    arms: Vec<Arm>,
+    stats: ModelStats,
+}
+
+#[derive(Default, Clone)]
+struct Arm {
+    trials: u64,
+    reward: f64,
+}
+
+impl Arm {
+    fn mean(&self) -> f64 {
+        if self.trials == 0 {
+            0.0
+        } else {
+            self.reward / self.trials as f64
+        }
+    }
+}
+
+struct PendingReward {
+    model_id: String,
+    choice: usize,
+    presented: bool,
+    expires: Duration,
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Simulator::new()
+    }
+}
+
+impl Simulator {
+    /// Creates a simulator seeded from the system clock
+    pub fn new() -> Simulator {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() ^ u64::from(d.subsec_nanos()))
+            .unwrap_or_default();
+        Simulator::with_seed(seed)
+    }
+
+    /// Creates a simulator whose predictions are determined entirely by `seed`
+    pub fn with_seed(seed: u64) -> Simulator {
+        Simulator {
+            state: Mutex::new(State {
+                rng: Rng::new(seed),
+                clock: Duration::from_secs(0),
+                next_model: 1,
+                next_reward: 1,
+                models: Vec::new(),
+                pending: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Moves the simulated clock forward, expiring any rewards that are now too old
+    pub fn advance(&self, by: Duration) {
+        let mut state = self.lock();
+        state.clock += by;
+        let now = state.clock;
+        state.pending.retain(|_, pending| pending.expires > now);
+    }
+
+    /// The time that has passed on the simulated clock since the simulator was created
+    pub fn elapsed(&self) -> Duration {
+        self.lock().clock
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // A panic while the lock was held cannot leave the state half updated in a way
+        // that matters to a test double, so carry on with it
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl SuggestionClient for Simulator {
+    fn create_model(&self, model: &Model) -> Result<Model> {
+        model.validate().map_err(|errors| Error::from(Kind::InvalidModel(errors)))?;
+        let mut state = self.lock();
+        let mut model = model.clone();
+        let id = match model.id {
+            Some(ref id) => id.clone(),
+            None => {
+                let id = format!("model{}", state.next_model);
+                state.next_model += 1;
+                id
+            }
+        };
+        if state.find(&id).is_some() {
+            return Err(Error::new(&format!("model {} already exists", id)));
+        }
+        model.id = Some(id);
+        model.options.get_or_insert_with(default_options);
+        state.models.push(SimulatedModel {
+            arms: vec![Arm::default(); model.choices.len()],
+            model: model.clone(),
+            stats: ModelStats {
+                predictions: 0,
+                rewards: 0,
+                reward_ratio: 0.0,
+                explores: 0,
+                exploits: 0,
+                explore_ratio: 0.0,
+            },
+        });
+        Ok(model)
+    }
+
+    fn delete_model(&self, id: &str) -> Result<()> {
+        let mut state = self.lock();
+        let index = state.find(id).ok_or_else(|| not_found(id))?;
+        state.models.remove(index);
+        state.pending.retain(|_, pending| pending.model_id != id);
+        Ok(())
+    }
+
+    fn get_model(&self, id: &str) -> Result<Model> {
+        let state = self.lock();
+        let index = state.find(id).ok_or_else(|| not_found(id))?;
+        Ok(state.models[index].model.clone())
+    }
+
+    fn list_models(&self) -> Result<Vec<Model>> {
+        Ok(self.lock().models.iter().map(|m| m.model.clone()).collect())
+    }
+
+    fn get_model_stats(&self, id: &str) -> Result<ModelStats> {
+        let state = self.lock();
+        let index = state.find(id).ok_or_else(|| not_found(id))?;
+        Ok(state.models[index].stats.clone())
+    }
+
+    fn predict(&self, model_id: &str, _request: &PredictionRequest) -> Result<PredictionResponse> {
+        let mut state = self.lock();
+        let index = state.find(model_id).ok_or_else(|| not_found(model_id))?;
+        let State {
+            ref mut rng,
+            ref mut models,
+            ..
+        } = *state;
+        let sim = &mut models[index];
+        let options = sim.model.options.clone().unwrap_or_else(default_options);
+
+        let scores: Vec<f64> = sim.arms.iter().map(Arm::mean).collect();
+        let mut ranked: Vec<usize> = (0..scores.len()).collect();
+        ranked.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+        let best = ranked[0];
+
+        let (chosen, explored) = if options.softmax_lambda > 0.0 {
+            let chosen = softmax(&scores, options.softmax_lambda, rng);
+            (chosen, scores[chosen] < scores[best])
+        } else if rng.next_f64() < options.epsilon {
+            (rng.below(scores.len()), true)
+        } else {
+            (best, false)
+        };
+        if explored {
+            sim.stats.explores += 1;
+        } else {
+            sim.stats.exploits += 1;
+        }
+        sim.stats.predictions += 1;
+        sim.stats.explore_ratio = sim.stats.explores as f64 / sim.stats.predictions as f64;
+        sim.stats.reward_ratio = sim.stats.rewards as f64 / sim.stats.predictions as f64;
+        sim.arms[chosen].trials += 1;
+
+        ranked.retain(|&i| i != chosen);
+        ranked.insert(0, chosen);
+
+        // Like suggestionbox, treat an expiration of 0 as a request for the default
+        let expiration = match options.reward_expiration_seconds {
+            0 => DEFAULT_REWARD_EXPIRATION_SECONDS,
+            secs => secs,
+        };
+        let expires = state.clock + Duration::from_secs(expiration);
+        let mut choices = Vec::with_capacity(ranked.len());
+        for (rank, &choice) in ranked.iter().enumerate() {
+            let reward_id = format!("reward{}", state.next_reward);
+            state.next_reward += 1;
+            state.pending.insert(
+                reward_id.clone(),
+                PendingReward {
+                    model_id: model_id.to_owned(),
+                    choice,
+                    presented: rank == 0,
+                    expires,
+                },
+            );
+            choices.push(Prediction {
+                id: state.models[index].model.choices[choice].id.clone(),
+                reward_id,
+                score: scores[choice],
+            });
+        }
+        Ok(PredictionResponse { choices })
+    }
+
+    fn reward(&self, model_id: &str, reward_id: &str, weight: f64) -> Result<()> {
+        let mut state = self.lock();
+        let index = state.find(model_id).ok_or_else(|| not_found(model_id))?;
+        let pending = match state.pending.get(reward_id) {
+            Some(pending) if pending.model_id == model_id => state.pending.remove(reward_id),
+            _ => None,
+        };
+        let pending = pending.ok_or_else(|| {
+            Error::new(&format!("reward {} is unknown or has expired", reward_id))
+        })?;
+
+        let sim = &mut state.models[index];
+        let arm = &mut sim.arms[pending.choice];
+        if !pending.presented {
+            arm.trials += 1;
+        }
+        arm.reward += weight;
+        sim.stats.rewards += 1;
+        sim.stats.reward_ratio = sim.stats.rewards as f64 / sim.stats.predictions.max(1) as f64;
+        Ok(())
+    }
+}
+
+impl State {
+    fn find(&self, id: &str) -> Option<usize> {
+        self.models
+            .iter()
+            .position(|m| m.model.id.as_ref().is_some_and(|model_id| model_id == id))
+    }
+}
+
+fn default_options() -> ModelOptions {
+    ModelOptions {
+        reward_expiration_seconds: DEFAULT_REWARD_EXPIRATION_SECONDS,
+        epsilon: DEFAULT_EPSILON,
+        softmax_lambda: 0.0,
+        ngrams: 1,
+        skipgrams: 0,
+    }
+}
+
+/// The error suggestionbox gives for a model it doesn't have
+fn not_found(id: &str) -> Error {
+    Error::from(Kind::Http {
+        status: 404,
+        body: format!("{{\"success\":false,\"error\":\"model {} not found\"}}", id),
+    })
+}
+
+/// Picks a choice with probability proportional to `exp(score / lambda)`
+fn softmax(scores: &[f64], lambda: f64, rng: &mut Rng) -> usize {
+    let max = scores.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let weights: Vec<f64> = scores.iter().map(|s| ((s - max) / lambda).exp()).collect();
+    let mut target = rng.next_f64() * weights.iter().sum::<f64>();
+    for (i, weight) in weights.iter().enumerate() {
+        if target < *weight {
+            return i;
+        }
+        target -= weight;
+    }
+    weights.len() - 1
+}
+
+/// A small xorshift generator, so that simulations are reproducible from a seed
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        // Run the seed through splitmix64, as xorshift must not start from zero
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        Rng((z ^ (z >> 31)) | 1)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.0 = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A number in the range [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A number in the range [0, n)
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_f64() * n as f64) as usize
+    }
+}
@@ -1,12 +1,19 @@
 extern crate mockito;
 
+use serde_json;
 use std;
-use self::mockito::{mock, SERVER_URL};
-use super::Suggestionbox;
-use super::ModelBuilder;
+use self::mockito::{mock, Matcher, SERVER_URL};
+use super::{RewardLedger, Simulator, SuggestionClient, Suggestionbox,
+            DEFAULT_REWARD_EXPIRATION_SECONDS};
+use super::{Choice, ChoiceChange, FeatureValue, Model, ModelBuilder, ModelDiff, ModelOptions,
+            ValidationError};
 use suggestionbox::Feature;
 use suggestionbox::PredictionRequest;
 use std::fs::File;
+use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use {Connection, Kind, RetryPolicy};
 
 #[test]
 fn create_model() {
@@ -32,7 +39,8 @@ fn create_model() {
             .id("testmodel")
             .choice("choice1", vec![Feature::text("foo", "bar")])
             .choice("choice2", vec![Feature::text("baz", "foo")])
-            .finish();
+            .finish()
+            .unwrap();
 
         let res = sb.create_model(&model);
         assert!(res.is_ok());
@@ -45,6 +53,126 @@ fn create_model() {
     mock.assert();
 }
 
+#[test]
+fn create_model_rejects_invalid_model() {
+    let mut model = ModelBuilder::new()
+        .choice("choice1", vec![Feature::number("age", 28.0)])
+        .finish()
+        .unwrap();
+    model.choices.push(Choice {
+        id: "choice1".to_owned(),
+        features: vec![Feature::number("age", f64::INFINITY)],
+    });
+    model.options = Some(ModelOptions {
+        reward_expiration_seconds: 120,
+        epsilon: 1.5,
+        softmax_lambda: 0.0,
+        ngrams: -1,
+        skipgrams: 0,
+    });
+
+    // No mock is registered, so the request must not be sent
+    let err = Suggestionbox::new(SERVER_URL).create_model(&model).unwrap_err();
+    match *err.kind() {
+        Kind::InvalidModel(ref errors) => assert_eq!(
+            errors,
+            &vec![
+                ValidationError::DuplicateChoice { choice: "choice1".to_owned() },
+                ValidationError::NotANumber {
+                    choice: "choice1".to_owned(),
+                    feature: "age".to_owned(),
+                    value: f64::INFINITY,
+                },
+                ValidationError::Epsilon(1.5),
+                ValidationError::Ngrams(-1),
+            ]
+        ),
+        ref other => panic!("unexpected error kind: {:?}", other),
+    }
+}
+
+#[test]
+fn model_builder_rejects_empty_model() {
+    let err = ModelBuilder::new().named("empty").finish().unwrap_err();
+    match *err.kind() {
+        Kind::InvalidModel(ref errors) => assert_eq!(errors, &vec![ValidationError::NoChoices]),
+        ref other => panic!("unexpected error kind: {:?}", other),
+    }
+    assert_eq!(err.to_string(), "Invalid model: the model has no choices");
+}
+
+#[test]
+fn features_round_trip() {
+    let request = PredictionRequest {
+        inputs: vec![
+            Feature::number("age", 0.1),
+            Feature::list("genres", vec!["action", "christmas"]),
+            Feature::keyword("country", "USA"),
+            Feature::image_url("poster", "https://test.machinebox.io/diehard.jpg"),
+        ],
+    };
+    let json = serde_json::to_value(&request).unwrap();
+    assert_eq!(json["inputs"][0]["value"], "0.1");
+    assert_eq!(json["inputs"][1]["value"], "action,christmas");
+
+    let decoded: PredictionRequest = serde_json::from_value(json).unwrap();
+    assert_eq!(decoded.inputs, request.inputs);
+    assert_eq!(decoded.input("age").and_then(FeatureValue::as_number), Some(0.1));
+    assert_eq!(
+        decoded.input("genres").and_then(FeatureValue::as_list),
+        Some(&["action".to_owned(), "christmas".to_owned()][..])
+    );
+    assert_eq!(decoded.input("country").and_then(FeatureValue::as_str), Some("USA"));
+    assert!(decoded.input("missing").is_none());
+}
+
+#[test]
+fn unjoinable_lists_are_rejected() {
+    let quotes = Feature::list("quotes", vec!["yippee-ki-yay, friend", " padded", "fine"]);
+    let res = serde_json::to_string(&quotes);
+    assert!(res.unwrap_err().to_string().contains("feature quotes"));
+
+    let choice = Choice {
+        id: "diehard".to_owned(),
+        features: vec![quotes],
+    };
+    assert_eq!(
+        choice.validate().unwrap_err(),
+        vec![
+            ValidationError::ListItem {
+                choice: "diehard".to_owned(),
+                feature: "quotes".to_owned(),
+                item: "yippee-ki-yay, friend".to_owned(),
+            },
+            ValidationError::ListItem {
+                choice: "diehard".to_owned(),
+                feature: "quotes".to_owned(),
+                item: " padded".to_owned(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn features_read_suggestionbox_format() {
+    let choice: Choice = serde_json::from_str(r#"{"id": "diehard", "features": [
+        {"key": "genres", "value": "action, christmas", "type": "list"},
+        {"key": "year", "value": "1988", "type": "number"},
+        {"key": "runtime", "value": 132, "type": "number"}
+    ]}"#).unwrap();
+    assert_eq!(
+        choice.feature("genres"),
+        Some(&FeatureValue::List(vec!["action".to_owned(), "christmas".to_owned()]))
+    );
+    assert_eq!(choice.feature("year"), Some(&FeatureValue::Number(1988.0)));
+    assert_eq!(choice.feature("runtime"), Some(&FeatureValue::Number(132.0)));
+
+    let malformed = serde_json::from_str::<Choice>(r#"{"id": "diehard", "features": [
+        {"key": "year", "value": "nineteen eighty-eight", "type": "number"}
+    ]}"#);
+    assert!(malformed.unwrap_err().to_string().contains("feature year"));
+}
+
 #[test]
 fn get_model() {
     let sb = Suggestionbox::new(SERVER_URL);
@@ -84,8 +212,27 @@ fn get_model_reports_failure() {
         .with_status(404)
         .create();
     {
-        let res = sb.get_model("model1");
-        assert!(res.is_err());
+        let err = sb.get_model("model1").unwrap_err();
+        assert!(err.is_not_found());
+        assert_eq!(err.status(), Some(404));
+        assert!(!err.is_retryable());
+    }
+    mock.assert();
+}
+
+#[test]
+fn delete_model_reports_box_error() {
+    let sb = Suggestionbox::new(SERVER_URL);
+    let mock = mock("DELETE", "/suggestionbox/models/model1")
+        .with_body(r#"{"success": false, "error": "no such model"}"#)
+        .create();
+    {
+        let err = sb.delete_model("model1").unwrap_err();
+        match *err.kind() {
+            Kind::Box(ref msg) => assert_eq!(msg, "no such model"),
+            ref other => panic!("unexpected error kind: {:?}", other),
+        }
+        assert_eq!(err.status(), None);
     }
     mock.assert();
 }
@@ -270,6 +417,51 @@ fn reward_reports_failure() {
     mock.assert();
 }
 
+#[test]
+fn reward_is_not_retried() {
+    let conn = Connection::builder(SERVER_URL)
+        .retry_policy(RetryPolicy {
+            initial_backoff: Duration::from_millis(1),
+            ..RetryPolicy::default()
+        })
+        .finish()
+        .unwrap();
+    let sb = Suggestionbox::with_connection(conn);
+    let mock = mock("POST", "/suggestionbox/models/model1/rewards")
+        .with_status(503)
+        .expect(1)
+        .create();
+    {
+        let res = sb.reward("model1", "8678dad8d1d8ad1bef77da", 1.0);
+        assert!(res.is_err());
+    }
+    mock.assert();
+}
+
+#[test]
+fn post_state_is_not_retried() {
+    // Uploading state creates a model, so repeating it could duplicate the model, even
+    // when operations that are not safe to repeat may be retried
+    let conn = Connection::builder(SERVER_URL)
+        .retry_policy(RetryPolicy {
+            initial_backoff: Duration::from_millis(1),
+            retry_non_idempotent: true,
+            ..RetryPolicy::default()
+        })
+        .finish()
+        .unwrap();
+    let sb = Suggestionbox::with_connection(conn);
+    let unavailable = mock("POST", "/suggestionbox/state")
+        .match_body("url=http%3A%2F%2Fstate%2Fnot-retried")
+        .with_status(503)
+        .expect(1)
+        .create();
+    {
+        assert!(sb.post_state_url("http://state/not-retried").is_err());
+    }
+    unavailable.assert();
+}
+
 #[test]
 fn download_state() {
     let sb = Suggestionbox::new(SERVER_URL);
@@ -332,6 +524,20 @@ fn post_state() {
     mock.assert();
 }
 
+#[test]
+fn post_state_reader() {
+    let sb = Suggestionbox::new(SERVER_URL);
+    let upload = mock("POST", "/suggestionbox/state")
+        .match_body(Matcher::Regex("(?s)name=\"state\".*statebytes".to_owned()))
+        .with_body(r#"{"success": true, "id": "model1", "name": "Articles", "choices": []}"#)
+        .create();
+    {
+        let model = sb.post_state_reader(Cursor::new(b"statebytes".to_vec())).unwrap();
+        assert_eq!(model.id, Some("model1".to_owned()));
+    }
+    upload.assert();
+}
+
 #[test]
 fn post_state_reports_failure() {
     let sb = Suggestionbox::new(SERVER_URL);
@@ -388,3 +594,408 @@ fn post_state_url_reports_error() {
     }
     mock.assert();
 }
+
+#[test]
+fn model_from_file_reports_corrupt_json() {
+    let path = std::env::temp_dir()
+        .join(format!("machinebox-corrupt-model-{}.json", std::process::id()));
+    std::fs::write(&path, r#"{"name": "Articles", "choices": ["#).unwrap();
+    let res = Model::from_file(&mut File::open(&path).unwrap());
+    std::fs::remove_file(&path).unwrap();
+    match *res.unwrap_err().kind() {
+        Kind::Deserialization { ref body, .. } => assert!(body.starts_with(r#"{"name""#)),
+        ref other => panic!("unexpected error kind: {:?}", other),
+    }
+}
+
+fn movies(options: ModelOptions) -> Model {
+    ModelBuilder::new()
+        .id("movies")
+        .choice("diehard", vec![Feature::keyword("genre", "action")])
+        .choice("elf", vec![Feature::keyword("genre", "comedy")])
+        .choice("alien", vec![Feature::keyword("genre", "horror")])
+        .options(options)
+        .finish()
+        .unwrap()
+}
+
+fn movie_options(epsilon: f64, softmax_lambda: f64) -> ModelOptions {
+    ModelOptions {
+        reward_expiration_seconds: 60,
+        epsilon,
+        softmax_lambda,
+        ngrams: 1,
+        skipgrams: 0,
+    }
+}
+
+/// Rewards every prediction of "elf" and returns the choices that were presented
+fn train(sim: &Simulator, rounds: usize) -> Vec<String> {
+    let request = PredictionRequest { inputs: Vec::new() };
+    (0..rounds)
+        .map(|_| {
+            let prediction = sim.predict("movies", &request).unwrap();
+            let top = &prediction.choices[0];
+            if top.id == "elf" {
+                sim.reward("movies", &top.reward_id, 1.0).unwrap();
+            }
+            top.id.clone()
+        })
+        .collect()
+}
+
+#[test]
+fn same_seed_same_predictions() {
+    let runs: Vec<Vec<String>> = (0..2)
+        .map(|_| {
+            let sim = Simulator::with_seed(7);
+            sim.create_model(&movies(movie_options(0.3, 0.0))).unwrap();
+            train(&sim, 50)
+        })
+        .collect();
+    assert_eq!(runs[0], runs[1]);
+}
+
+#[test]
+fn epsilon_greedy_learns_and_explores() {
+    let sim = Simulator::with_seed(1);
+    sim.create_model(&movies(movie_options(0.2, 0.0))).unwrap();
+    let presented = train(&sim, 500);
+    let elf = presented[250..].iter().filter(|id| *id == "elf").count();
+    assert!(elf > 180 && elf < 250, "elf presented {} times", elf);
+
+    let stats = sim.get_model_stats("movies").unwrap();
+    assert_eq!(stats.predictions, 500);
+    assert_eq!(stats.explores + stats.exploits, 500);
+    assert!((stats.explore_ratio - 0.2).abs() < 0.05, "{:?}", stats);
+    assert_eq!(stats.rewards as usize, presented.iter().filter(|id| *id == "elf").count());
+}
+
+#[test]
+fn softmax_favours_rewarded_choice() {
+    let sim = Simulator::with_seed(3);
+    sim.create_model(&movies(movie_options(0.0, 0.1))).unwrap();
+    let presented = train(&sim, 300);
+    let elf = presented[150..].iter().filter(|id| *id == "elf").count();
+    assert!(elf > 130, "elf presented {} times", elf);
+}
+
+#[test]
+fn rewards_expire() {
+    let sim = Simulator::with_seed(5);
+    sim.create_model(&movies(movie_options(0.3, 0.0))).unwrap();
+    let request = PredictionRequest { inputs: Vec::new() };
+    let prediction = sim.predict("movies", &request).unwrap();
+
+    sim.advance(Duration::from_secs(59));
+    sim.reward("movies", &prediction.choices[0].reward_id, 1.0).unwrap();
+    sim.advance(Duration::from_secs(1));
+    assert!(sim.reward("movies", &prediction.choices[1].reward_id, 1.0).is_err());
+    assert_eq!(sim.elapsed(), Duration::from_secs(60));
+
+    // A reward can only be claimed once
+    assert!(sim.reward("movies", &prediction.choices[0].reward_id, 1.0).is_err());
+}
+
+#[test]
+fn zero_reward_expiration_uses_default() {
+    let sim = Simulator::with_seed(5);
+    let mut model = movies(movie_options(0.3, 0.0));
+    model.options.as_mut().unwrap().reward_expiration_seconds = 0;
+    sim.create_model(&model).unwrap();
+    let request = PredictionRequest { inputs: Vec::new() };
+    let prediction = sim.predict("movies", &request).unwrap();
+
+    sim.advance(Duration::from_secs(29));
+    sim.reward("movies", &prediction.choices[0].reward_id, 1.0).unwrap();
+    sim.advance(Duration::from_secs(1));
+    assert!(sim.reward("movies", &prediction.choices[1].reward_id, 1.0).is_err());
+}
+
+#[test]
+fn unknown_models_are_not_found() {
+    let sim = Simulator::with_seed(9);
+    let request = PredictionRequest { inputs: Vec::new() };
+    assert!(sim.predict("movies", &request).unwrap_err().is_not_found());
+
+    let created = sim.create_model(&movies(movie_options(0.3, 0.0))).unwrap();
+    assert_eq!(created.id, Some("movies".to_owned()));
+    match *sim.create_model(&created).unwrap_err().kind() {
+        Kind::Box(ref msg) => assert_eq!(msg, "model movies already exists"),
+        ref other => panic!("unexpected error kind: {:?}", other),
+    }
+    assert_eq!(sim.list_models().unwrap().len(), 1);
+    sim.delete_model("movies").unwrap();
+    assert!(sim.get_model("movies").unwrap_err().is_not_found());
+}
+
+type Clock = Arc<Mutex<SystemTime>>;
+
+/// A simulator with one model, and a ledger whose clock is moved by `advance`
+fn ledger_setup() -> (Simulator, RewardLedger, Clock) {
+    let sim = Simulator::with_seed(11);
+    let model = ModelBuilder::new()
+        .id("movies")
+        .choice("diehard", vec![Feature::keyword("genre", "action")])
+        .choice("elf", vec![Feature::keyword("genre", "comedy")])
+        .options(ModelOptions {
+            reward_expiration_seconds: 60,
+            epsilon: 0.3,
+            softmax_lambda: 0.0,
+            ngrams: 1,
+            skipgrams: 0,
+        })
+        .finish()
+        .unwrap();
+    sim.create_model(&model).unwrap();
+
+    let clock = Arc::new(Mutex::new(UNIX_EPOCH));
+    let now = Arc::clone(&clock);
+    let ledger = RewardLedger::with_clock(move || *now.lock().unwrap());
+    (sim, ledger, clock)
+}
+
+/// Moves the simulator and the ledger's clock forward together
+fn advance(sim: &Simulator, clock: &Clock, secs: u64) {
+    *clock.lock().unwrap() += Duration::from_secs(secs);
+    sim.advance(Duration::from_secs(secs));
+}
+
+#[test]
+fn records_rewards_and_reports_coverage() {
+    let (sim, mut ledger, clock) = ledger_setup();
+    let request = PredictionRequest { inputs: Vec::new() };
+    let first = ledger.predict(&sim, "movies", &request).unwrap();
+    advance(&sim, &clock, 10);
+    let second = ledger.predict(&sim, "movies", &request).unwrap();
+    ledger.predict(&sim, "movies", &request).unwrap();
+
+    let reward_id = &first.choices[1].reward_id;
+    ledger.reward(&sim, "movies", reward_id, 1.0).unwrap();
+    let record = ledger.prediction(reward_id).unwrap();
+    assert_eq!(record.predicted_at, UNIX_EPOCH);
+    assert_eq!(record.expires_at, Some(UNIX_EPOCH + Duration::from_secs(60)));
+    let reward = record.reward.as_ref().unwrap();
+    assert_eq!(reward.choice_id, first.choices[1].id);
+    assert_eq!(reward.rewarded_at, UNIX_EPOCH + Duration::from_secs(10));
+
+    let unrewarded = ledger.unrewarded();
+    assert_eq!(unrewarded.len(), 2);
+    assert_eq!(unrewarded[0].choices[0].reward_id, second.choices[0].reward_id);
+
+    let coverage = ledger.coverage();
+    assert_eq!((coverage.predictions, coverage.rewarded, coverage.pending), (3, 1, 2));
+    assert!((coverage.ratio() - 1.0 / 3.0).abs() < 1e-9);
+    assert_eq!(ledger.model_coverage("books").predictions, 0);
+}
+
+#[test]
+fn refuses_expired_rewards() {
+    let (sim, mut ledger, clock) = ledger_setup();
+    let request = PredictionRequest { inputs: Vec::new() };
+    let prediction = ledger.predict(&sim, "movies", &request).unwrap();
+    advance(&sim, &clock, 60);
+
+    let reward_id = &prediction.choices[0].reward_id;
+    match *ledger.reward(&sim, "movies", reward_id, 1.0).unwrap_err().kind() {
+        Kind::RewardExpired { ref model_id, reward_id: ref id } => {
+            assert_eq!(model_id, "movies");
+            assert_eq!(id, reward_id);
+        }
+        ref other => panic!("unexpected error kind: {:?}", other),
+    }
+    assert_eq!(sim.get_model_stats("movies").unwrap().rewards, 0);
+    assert_eq!(ledger.coverage().expired, 1);
+
+    let settled = ledger.drain_settled();
+    assert_eq!(settled.len(), 1);
+    assert!(ledger.predictions().is_empty());
+    assert!(ledger.prediction(reward_id).is_none());
+
+    // Draining the prediction doesn't let its rewards through
+    match *ledger.reward(&sim, "movies", reward_id, 1.0).unwrap_err().kind() {
+        Kind::RewardExpired { .. } => {}
+        ref other => panic!("unexpected error kind: {:?}", other),
+    }
+    assert_eq!(sim.get_model_stats("movies").unwrap().rewards, 0);
+}
+
+#[test]
+fn refuses_second_rewards() {
+    let (sim, mut ledger, _) = ledger_setup();
+    let request = PredictionRequest { inputs: Vec::new() };
+    let prediction = ledger.predict(&sim, "movies", &request).unwrap();
+    let first = &prediction.choices[0].reward_id;
+    let second = &prediction.choices[1].reward_id;
+    ledger.reward(&sim, "movies", first, 1.0).unwrap();
+
+    let refused = |ledger: &mut RewardLedger, reward_id: &str| {
+        match *ledger.reward(&sim, "movies", reward_id, 2.0).unwrap_err().kind() {
+            Kind::AlreadyRewarded { ref model_id, reward_id: ref id } => {
+                assert_eq!((model_id.as_str(), id.as_str()), ("movies", reward_id));
+            }
+            ref other => panic!("unexpected error kind: {:?}", other),
+        }
+    };
+    refused(&mut ledger, first);
+    refused(&mut ledger, second);
+    let reward = ledger.prediction(first).unwrap().reward.clone().unwrap();
+    assert_eq!((reward.reward_id.as_str(), reward.weight), (first.as_str(), 1.0));
+
+    assert_eq!(ledger.drain_settled().len(), 1);
+    refused(&mut ledger, second);
+    assert_eq!(sim.get_model_stats("movies").unwrap().rewards, 1);
+}
+
+#[test]
+fn drained_predictions_are_forgotten_after_grace_period() {
+    let (sim, mut ledger, clock) = ledger_setup();
+    ledger.set_grace_period(Duration::from_secs(30));
+    let request = PredictionRequest { inputs: Vec::new() };
+    let rewarded = ledger.predict(&sim, "movies", &request).unwrap();
+    ledger.reward(&sim, "movies", &rewarded.choices[0].reward_id, 1.0).unwrap();
+    ledger.predict(&sim, "movies", &request).unwrap();
+    assert_eq!(ledger.tracked_reward_ids(), 4);
+
+    // Drained predictions are remembered until their expiry plus the grace period
+    advance(&sim, &clock, 60);
+    assert_eq!(ledger.drain_settled().len(), 2);
+    assert_eq!(ledger.tracked_reward_ids(), 4);
+    advance(&sim, &clock, 29);
+    assert!(ledger.drain_settled().is_empty());
+    assert_eq!(ledger.tracked_reward_ids(), 4);
+    advance(&sim, &clock, 1);
+    assert!(ledger.drain_settled().is_empty());
+    assert_eq!(ledger.tracked_reward_ids(), 0);
+
+    // What was drained still counts towards coverage
+    let coverage = ledger.coverage();
+    assert_eq!((coverage.predictions, coverage.rewarded, coverage.expired), (2, 1, 1));
+    assert_eq!(ledger.model_coverage("movies"), coverage);
+    assert_eq!(ledger.model_coverage("books").predictions, 0);
+}
+
+#[test]
+fn recorded_predictions_use_known_expiration() {
+    let (sim, mut ledger, clock) = ledger_setup();
+    let request = PredictionRequest { inputs: Vec::new() };
+    let unknown = sim.predict("movies", &request).unwrap();
+    ledger.record("movies", &unknown);
+    ledger.set_expiration("movies", Duration::from_secs(5));
+    let known = sim.predict("movies", &request).unwrap();
+    ledger.record("movies", &known);
+    advance(&sim, &clock, 5);
+
+    assert!(ledger.prediction(&unknown.choices[0].reward_id).unwrap().expires_at.is_none());
+    let record = ledger.prediction(&known.choices[0].reward_id).unwrap();
+    assert!(record.is_expired(UNIX_EPOCH + Duration::from_secs(5)));
+    assert!(!record.is_expired(SystemTime::UNIX_EPOCH));
+    assert_eq!(ledger.coverage().pending, 1);
+}
+
+#[test]
+fn refuses_rewards_for_other_models() {
+    let (sim, mut ledger, _) = ledger_setup();
+    let request = PredictionRequest { inputs: Vec::new() };
+    let prediction = ledger.predict(&sim, "movies", &request).unwrap();
+
+    let reward_id = &prediction.choices[0].reward_id;
+    match *ledger.reward(&sim, "books", reward_id, 1.0).unwrap_err().kind() {
+        Kind::RewardModelMismatch { ref model_id, ref expected, .. } => {
+            assert_eq!((model_id.as_str(), expected.as_str()), ("books", "movies"));
+        }
+        ref other => panic!("unexpected error kind: {:?}", other),
+    }
+    assert!(ledger.prediction(reward_id).unwrap().reward.is_none());
+    ledger.reward(&sim, "movies", reward_id, 1.0).unwrap();
+}
+
+#[test]
+fn zero_expiration_uses_box_default() {
+    let (sim, mut ledger, clock) = ledger_setup();
+    let mut model = sim.get_model("movies").unwrap();
+    model.id = Some("shows".to_owned());
+    model.options.as_mut().unwrap().reward_expiration_seconds = 0;
+    sim.create_model(&model).unwrap();
+    let request = PredictionRequest { inputs: Vec::new() };
+    let rewarded = ledger.predict(&sim, "shows", &request).unwrap();
+    let expired = ledger.predict(&sim, "shows", &request).unwrap();
+    advance(&sim, &clock, 20);
+
+    let reward_id = &rewarded.choices[0].reward_id;
+    let expiration = Duration::from_secs(DEFAULT_REWARD_EXPIRATION_SECONDS);
+    assert_eq!(ledger.prediction(reward_id).unwrap().expires_at, Some(UNIX_EPOCH + expiration));
+    ledger.reward(&sim, "shows", reward_id, 1.0).unwrap();
+
+    // The simulator and the ledger agree on when the default expiration runs out
+    advance(&sim, &clock, DEFAULT_REWARD_EXPIRATION_SECONDS - 20);
+    let reward_id = &expired.choices[0].reward_id;
+    match *ledger.reward(&sim, "shows", reward_id, 1.0).unwrap_err().kind() {
+        Kind::RewardExpired { .. } => {}
+        ref other => panic!("unexpected error kind: {:?}", other),
+    }
+    assert!(sim.reward("shows", reward_id, 1.0).is_err());
+}
+
+#[test]
+fn computes_choice_changes() {
+    let remote = ModelBuilder::new()
+        .named("Articles")
+        .choice("article1", vec![Feature::text("title", "Machine Box releases new product")])
+        .choice("article2", vec![Feature::text("title", "The beatles reunite")])
+        .choice("article3", vec![Feature::number("words", 800.0)])
+        .finish()
+        .unwrap();
+    let local = ModelBuilder::new()
+        .named("Articles")
+        .choice("article4", vec![Feature::keyword("section", "sport")])
+        .choice("article2", vec![Feature::text("title", "The beatles split")])
+        .choice("article1", vec![Feature::text("title", "Machine Box releases new product")])
+        .finish()
+        .unwrap();
+
+    let diff = ModelDiff::between(&remote, &local);
+    assert_eq!(
+        diff.choices,
+        vec![
+            ChoiceChange::Remove("article3".to_owned()),
+            ChoiceChange::ReplaceFeatures {
+                id: "article2".to_owned(),
+                features: vec![Feature::text("title", "The beatles split")],
+            },
+            ChoiceChange::Add(local.choices[0].clone()),
+        ]
+    );
+    assert!(!diff.name_changed && !diff.options_changed);
+    assert!(ModelDiff::between(&local, &local).is_empty());
+
+    let applied = diff.apply(&remote);
+    assert_eq!(applied.name, remote.name);
+    assert!(ModelDiff::between(&applied, &local).is_empty());
+}
+
+#[test]
+fn only_set_options_are_compared() {
+    let mut remote = ModelBuilder::new()
+        .choice("article1", vec![])
+        .finish()
+        .unwrap();
+    remote.options = Some(ModelOptions {
+        reward_expiration_seconds: 30,
+        epsilon: 0.3,
+        softmax_lambda: 0.0,
+        ngrams: 1,
+        skipgrams: 0,
+    });
+    let mut local = remote.clone();
+    local.options = None;
+    assert!(ModelDiff::between(&remote, &local).is_empty());
+
+    local.name = "renamed".to_owned();
+    local.options = remote.options.clone().map(|mut options| {
+        options.epsilon = 0.1;
+        options
+    });
+    let diff = ModelDiff::between(&remote, &local);
+    assert!(diff.name_changed && diff.options_changed && diff.choices.is_empty());
+}
@@ -1,8 +1,12 @@
 use super::Result;
-use serde_json;
+use std::collections::HashSet;
+use std::fmt;
 use std::fs::File;
 use std::io::Read;
-use super::{Kind, Error};
+use utils::{box_error, parse};
+use super::feature::joinable;
+use super::{Feature, FeatureValue};
+use {Error, Kind};
 
 /// A model represents a single model inside Suggestionbox
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -20,12 +24,137 @@ pub struct Model {
 }
 
 impl Model {
-    /// Convenience method to create a new model from a JSON file
+    /// Convenience method to create a new model from a JSON file. A file that isn't a
+    /// valid model fails with `Kind::Deserialization`.
     pub fn from_file(f: &mut File) -> Result<Model> {
         let mut contents = String::new();
         f.read_to_string(&mut contents)?;
-        let m: Model = serde_json::from_str(&contents)?;
-        Ok(m)
+        parse(&contents)
+    }
+
+    /// Checks the model for mistakes that suggestionbox would reject, such as duplicate
+    /// choice IDs or numeric features that aren't numbers. Returns every problem found,
+    /// rather than stopping at the first.
+    pub fn validate(&self) -> ::std::result::Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        if self.choices.is_empty() {
+            errors.push(ValidationError::NoChoices);
+        }
+
+        let mut seen = HashSet::new();
+        for choice in &self.choices {
+            if !choice.id.is_empty() && !seen.insert(choice.id.as_str()) {
+                errors.push(ValidationError::DuplicateChoice {
+                    choice: choice.id.clone(),
+                });
+            }
+            choice.check(&mut errors);
+        }
+
+        if let Some(ref options) = self.options {
+            if !(0.0..=1.0).contains(&options.epsilon) {
+                errors.push(ValidationError::Epsilon(options.epsilon));
+            }
+            if options.softmax_lambda.is_nan() || options.softmax_lambda < 0.0 {
+                errors.push(ValidationError::SoftmaxLambda(options.softmax_lambda));
+            }
+            if options.ngrams < 0 {
+                errors.push(ValidationError::Ngrams(options.ngrams));
+            }
+            if options.skipgrams < 0 {
+                errors.push(ValidationError::Skipgrams(options.skipgrams));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// A problem with a model that would cause suggestionbox to reject it
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    /// The model has no choices to select from
+    NoChoices,
+    /// A choice has an empty ID
+    EmptyChoiceId,
+    /// More than one choice has the same ID
+    DuplicateChoice {
+        /// The repeated choice ID
+        choice: String,
+    },
+    /// A feature of a choice has an empty key
+    EmptyFeatureKey {
+        /// The ID of the choice with the feature
+        choice: String,
+    },
+    /// A numeric feature is NaN or infinite
+    NotANumber {
+        /// The ID of the choice with the feature
+        choice: String,
+        /// The key of the feature
+        feature: String,
+        /// The offending value
+        value: f64,
+    },
+    /// An item of a list feature can't be sent as part of a comma separated list, because
+    /// it is empty, contains a comma or is padded with whitespace
+    ListItem {
+        /// The ID of the choice with the feature
+        choice: String,
+        /// The key of the feature
+        feature: String,
+        /// The offending item
+        item: String,
+    },
+    /// `epsilon` lies outside 0..=1
+    Epsilon(f64),
+    /// `softmax_lambda` is negative, or not a number
+    SoftmaxLambda(f64),
+    /// `ngrams` is negative
+    Ngrams(i32),
+    /// `skipgrams` is negative
+    Skipgrams(i32),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ValidationError::NoChoices => f.write_str("the model has no choices"),
+            ValidationError::EmptyChoiceId => f.write_str("a choice has an empty ID"),
+            ValidationError::DuplicateChoice { ref choice } => {
+                write!(f, "choice {} appears more than once", choice)
+            }
+            ValidationError::EmptyFeatureKey { ref choice } => {
+                write!(f, "choice {} has a feature with an empty key", choice)
+            }
+            ValidationError::NotANumber { ref choice, ref feature, ref value } => write!(
+                f,
+                "feature {} of choice {} must be a finite number, got {}",
+                feature, choice, value
+            ),
+            ValidationError::ListItem { ref choice, ref feature, ref item } => write!(
+                f,
+                "list feature {} of choice {} has the item {:?}, but items must be non-empty, \
+                 without commas and without surrounding whitespace",
+                feature, choice, item
+            ),
+            ValidationError::Epsilon(epsilon) => {
+                write!(f, "epsilon must be between 0 and 1, got {}", epsilon)
+            }
+            ValidationError::SoftmaxLambda(lambda) => {
+                write!(f, "softmax_lambda must not be negative, got {}", lambda)
+            }
+            ValidationError::Ngrams(ngrams) => {
+                write!(f, "ngrams must not be negative, got {}", ngrams)
+            }
+            ValidationError::Skipgrams(skipgrams) => {
+                write!(f, "skipgrams must not be negative, got {}", skipgrams)
+            }
+        }
     }
 }
 
@@ -44,7 +173,8 @@ impl Model {
 ///                 .choice(
 ///                    "article2",
 ///                    vec![Feature::text("title", "The beatles reunite")])
-///                 .finish();
+///                 .finish()
+///                 .unwrap();
 /// ```
 pub struct ModelBuilder {
     name: String,
@@ -53,6 +183,12 @@ pub struct ModelBuilder {
     options: Option<ModelOptions>,
 }
 
+impl Default for ModelBuilder {
+    fn default() -> Self {
+        ModelBuilder::new()
+    }
+}
+
 impl ModelBuilder {
     /// Creates a new modelbuilder with reasonable defaults set
     pub fn new() -> ModelBuilder {
@@ -81,7 +217,7 @@ impl ModelBuilder {
     pub fn choice(mut self, id: &str, features: Vec<Feature>) -> ModelBuilder {
         self.choices.push(Choice {
             id: id.to_owned(),
-            features: features,
+            features,
         });
         self
     }
@@ -94,20 +230,22 @@ impl ModelBuilder {
 
     /// Creates a new from the builder. As indicated by the name `finish`, this builder
     /// will be unusable after this method call as its values will have moved into the new
-    /// `Model`.
-    pub fn finish(self) -> Model {
-        Model {
+    /// `Model`. Fails with `Kind::InvalidModel` if the model doesn't pass `Model::validate`.
+    pub fn finish(self) -> Result<Model> {
+        let model = Model {
             name: self.name,
             id: self.id,
             choices: self.choices,
             options: self.options,
-        }
+        };
+        model.validate().map_err(|errors| Error::from(Kind::InvalidModel(errors)))?;
+        Ok(model)
     }
 }
 
 /// Various configuration parameters that can be used to tweak the behavior and learning
 /// options of the suggestionbox model.
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct ModelOptions {
     /// Determines the maximum length of time in seconds between when a prediction is presented
     /// to a client and when a reward is posted.
@@ -128,7 +266,7 @@ pub struct ModelOptions {
 }
 
 /// A choice represents a value that can be predicted for a user, and includes a set of features
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Choice {
     /// The ID of the choice
     pub id: String,
@@ -136,95 +274,52 @@ pub struct Choice {
     pub features: Vec<Feature>,
 }
 
-/// Tells suggestionbox how to treat the feature value when making predictions
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub enum FeatureType {
-    /// Indicates a numerical feature
-    #[serde(rename = "number")]
-    Number,
-    /// Indicates a textual feature that will be tokenized. Use `Keyword` if you don't want
-    /// tokenization.
-    #[serde(rename = "text")]
-    Text,
-    /// Indicates a non-tokenized textual feature. A `list` can be used to supply multiple
-    /// keywords as a feature value
-    #[serde(rename = "keyword")]
-    Keyword,
-    /// Indicates a list of non-tokenized keywords.
-    #[serde(rename = "list")]
-    List,
-    /// Indicates a feature value that is a URL
-    #[serde(rename = "image_url")]
-    ImageURL,
-    /// Indicates a feature value that is a binary image encoded with Base64
-    #[serde(rename = "image_base64")]
-    ImageBase64,
-}
-
-/// A feature is used to describe an input or a choice. For example, age:28 or location:"London"
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Feature {
-    /// The feature's key
-    pub key: String,
-    /// The value of the feature
-    pub value: String,
-    /// The type of this feature
-    #[serde(rename = "type")]
-    pub feature_type: FeatureType,
-}
-
-impl Feature {
-    /// Shortcut for producing a new text feature
-    pub fn text(key: &str, text: &str) -> Feature {
-        Feature {
-            key: key.to_owned(),
-            value: text.to_owned(),
-            feature_type: FeatureType::Text,
-        }
+impl Choice {
+    /// The value of the feature with the given key
+    pub fn feature(&self, key: &str) -> Option<&FeatureValue> {
+        find_feature(&self.features, key)
     }
 
-    /// Shortcut for production a numerical feature
-    pub fn number(key: &str, number: f64) -> Feature {
-        Feature {
-            key: key.to_owned(),
-            value: format!("{}", number),
-            feature_type: FeatureType::Number,
-        }
-    }
-
-    /// Shortcut for producing a keyword feature
-    pub fn keyword(key: &str, keyword: &str) -> Feature {
-        Feature {
-            key: key.to_owned(),
-            value: keyword.to_owned(),
-            feature_type: FeatureType::Keyword,
-        }
-    }
-
-    /// Shortcut for producing a keyword list feature
-    pub fn list(key: &str, list: Vec<&str>) -> Feature {
-        Feature {
-            key: key.to_owned(),
-            value: list.join(","),
-            feature_type: FeatureType::List,
+    /// Checks the choice for the mistakes that `Model::validate` looks for in each choice
+    pub fn validate(&self) -> ::std::result::Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        self.check(&mut errors);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
         }
     }
 
-    /// Shortcut for producing an image URL feature
-    pub fn image_url(key: &str, url: &str) -> Feature {
-        Feature {
-            key: key.to_owned(),
-            value: url.to_owned(),
-            feature_type: FeatureType::ImageURL,
+    fn check(&self, errors: &mut Vec<ValidationError>) {
+        if self.id.is_empty() {
+            errors.push(ValidationError::EmptyChoiceId);
         }
-    }
-
-    /// Shortcut for producing a base64-encoded image feature
-    pub fn image_base64(key: &str, data: &str) -> Feature {
-        Feature {
-            key: key.to_owned(),
-            value: data.to_owned(),
-            feature_type: FeatureType::ImageBase64,
+        for feature in &self.features {
+            if feature.key.is_empty() {
+                errors.push(ValidationError::EmptyFeatureKey {
+                    choice: self.id.clone(),
+                });
+            }
+            match feature.value {
+                FeatureValue::Number(value) if !value.is_finite() => {
+                    errors.push(ValidationError::NotANumber {
+                        choice: self.id.clone(),
+                        feature: feature.key.clone(),
+                        value,
+                    });
+                }
+                FeatureValue::List(ref items) => {
+                    for item in items.iter().filter(|item| !joinable(item)) {
+                        errors.push(ValidationError::ListItem {
+                            choice: self.id.clone(),
+                            feature: feature.key.clone(),
+                            item: item.clone(),
+                        });
+                    }
+                }
+                _ => {}
+            }
         }
     }
 }
@@ -255,18 +350,12 @@ pub struct ModelList {
     pub error: Option<String>,
 }
 
-impl Into<Result<Vec<Model>>> for ModelList {
-    fn into(self) -> Result<Vec<Model>> {
-        if self.success {
-            Ok(self.models)
+impl From<ModelList> for Result<Vec<Model>> {
+    fn from(response: ModelList) -> Result<Vec<Model>> {
+        if response.success {
+            Ok(response.models)
         } else {
-            let s = match self.error {
-                Some(s) => s,
-                None => "Request failed".to_owned(),
-            };
-            Err(Error {
-                kind: Kind::Machinebox(s),
-            })
+            Err(box_error(response.error))
         }
     }
 }
@@ -281,18 +370,12 @@ pub struct PredictionResponseFull {
     pub choices: Vec<Prediction>,
 }
 
-impl Into<Result<PredictionResponse>> for PredictionResponseFull {
-    fn into(self) -> Result<PredictionResponse> {
-        if self.success {
-            Ok(PredictionResponse { choices: self.choices })
+impl From<PredictionResponseFull> for Result<PredictionResponse> {
+    fn from(response: PredictionResponseFull) -> Result<PredictionResponse> {
+        if response.success {
+            Ok(PredictionResponse { choices: response.choices })
         } else {
-            let s = match self.error {
-                Some(s) => s,
-                None => "Request failed".to_owned(),
-            };
-            Err(Error {
-                kind: Kind::Machinebox(s),
-            })
+            Err(box_error(response.error))
         }
     }
 }
@@ -324,6 +407,17 @@ pub struct PredictionRequest {
     pub inputs: Vec<Feature>,
 }
 
+impl PredictionRequest {
+    /// The value of the input with the given key
+    pub fn input(&self, key: &str) -> Option<&FeatureValue> {
+        find_feature(&self.inputs, key)
+    }
+}
+
+fn find_feature<'a>(features: &'a [Feature], key: &str) -> Option<&'a FeatureValue> {
+    features.iter().find(|f| f.key == key).map(|f| &f.value)
+}
+
 /// A reward is used to inform the suggestionbox of a successful prediction.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Reward {
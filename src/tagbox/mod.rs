@@ -3,88 +3,74 @@
 //! **Tagbox** lets you identify the content of images by getting a list of ordered tags.
 //!
 //! For more information, see the [tagbox docs](https://machinebox.io/docs/tagbox)
-use std::io::Write;
-use super::BoxClient;
+use std::io::{Read, Write};
+use super::{BoxClient, Connection, ImageSource};
 use super::Result;
-use reqwest;
-use reqwest::StatusCode;
-use serde_json;
-use Error;
-use Kind;
 
 pub use self::types::{CheckResponse, Tag};
 use self::types::{CheckResponseFull, SimilarResponse, TeachResponse, TrainTag};
 
-use super::utils::{delete_with_response, patch_json, post_form_vars, post_json,
-                   post_multipart_file, post_multipart_reader, URLWrapper};
-use std::io::Read;
+use super::utils::{delete_with_response, patch_json, post_form_vars, post_image,
+                   post_image_parts, post_json, post_multipart_file, post_multipart_reader_field,
+                   get_stream, parse, Retry};
 use utils::RawBoxResponse;
 
 /// The client for the `tagbox` machine box.
 pub struct Tagbox {
-    url: String,
+    conn: Connection,
 }
 
 impl Tagbox {
     /// Creates a new tagbox client connecting to the supplied URL.
     pub fn new(url: &str) -> Tagbox {
-        Tagbox { url: url.to_owned() }
+        Tagbox::with_connection(Connection::new(url))
     }
 
-    /// Gets the tags for the image to which `reader` points
-    pub fn check<T: Read + Send + 'static>(&self, reader: T) -> Result<CheckResponse> {
-        let url = format!("{}/tagbox/check", self.url());
-        let raw = post_multipart_reader(&url, reader)?;
-        let checkreply: CheckResponseFull = serde_json::from_str(&raw)?;
-        checkreply.into()
+    /// Creates a new tagbox client that makes all of its requests through the
+    /// supplied connection
+    pub fn with_connection(conn: Connection) -> Tagbox {
+        Tagbox { conn }
     }
 
-    /// Gets the tags for the image at `source_path`
-    pub fn check_path(&self, source_path: &str) -> Result<CheckResponse> {
+    /// Gets the tags for the supplied image
+    pub fn check(&self, image: ImageSource) -> Result<CheckResponse> {
         let url = format!("{}/tagbox/check", self.url());
-        let raw = post_multipart_file(&url, source_path)?;
-        let checkreply: CheckResponseFull = serde_json::from_str(&raw)?;
+        let raw = post_image(&self.conn, &url, image, Retry::Idempotent)?;
+        let checkreply: CheckResponseFull = parse(&raw)?;
         checkreply.into()
     }
 
-    /// Gets the tags for the image contained in the base64 encoded data
-    pub fn check_base64(&self, data: &str) -> Result<CheckResponse> {
-        let url = format!("{}/tagbox/check", self.url());
-        let params = [("base64", data)];
-        let s = post_form_vars(&url, &params)?;
-        let checkreply: CheckResponseFull = serde_json::from_str(&s)?;
-        checkreply.into()
-    }
-
-    /// Gets the tags for the image at the given URL
-    pub fn check_url(&self, image_url: &str) -> Result<CheckResponse> {
-        let url = format!("{}/tagbox/check", self.url());
-        let params = URLWrapper {
-            url: image_url.to_owned(),
-        };
-        let s = post_json(&url, &params)?;
-        let checkreply: CheckResponseFull = serde_json::from_str(&s)?;
-        checkreply.into()
-    }
-
-    /// Teaches the tagbox the image with a custom tag at the specified URL
-    pub fn teach_url(&self, image_url: &str, tag: &str, id: Option<String>) -> Result<()> {
+    /// Teaches the tagbox the supplied image with a custom tag. Images on local disk, or
+    /// anywhere else the box cannot reach, are uploaded along with the tag. If no `id` is
+    /// given, the box assigns one.
+    pub fn teach(&self, image: ImageSource, tag: &str, id: Option<&str>) -> Result<()> {
         let url = format!("{}/tagbox/teach", self.url());
-        let train = TrainTag {
-            url: image_url.to_owned(),
-            id: id,
-            tag: tag.to_owned(),
+        let s = match image {
+            ImageSource::Url(image_url) => {
+                let train = TrainTag {
+                    url: image_url,
+                    id: id.map(str::to_owned),
+                    tag: tag.to_owned(),
+                };
+                post_json(&self.conn, &url, &train, Retry::NonIdempotent)?
+            }
+            image => {
+                let mut fields = vec![("tag", tag)];
+                if let Some(id) = id {
+                    fields.push(("id", id));
+                }
+                post_image_parts(&self.conn, &url, image, &fields, Retry::NonIdempotent)?
+            }
         };
-        let s = post_json(&url, &train)?;
-        let teachreply: TeachResponse = serde_json::from_str(&s)?;
+        let teachreply: TeachResponse = parse(&s)?;
         teachreply.into()
     }
 
     /// Deletes a custom tag by its ID
     pub fn remove_custom_tag(&self, id: &str) -> Result<()> {
         let url = format!("{}/tagbox/teach/{}", self.url(), id);
-        let s = delete_with_response(&url)?;
-        let teachreply: TeachResponse = serde_json::from_str(&s)?;
+        let s = delete_with_response(&self.conn, &url)?;
+        let teachreply: TeachResponse = parse(&s)?;
         teachreply.into()
     }
 
@@ -96,37 +82,21 @@ impl Tagbox {
             id: None,
             confidence: None,
         };
-        let s = patch_json(&url, &tag)?;
-        let teachreply: TeachResponse = serde_json::from_str(&s)?;
+        let s = patch_json(&self.conn, &url, &tag)?;
+        let teachreply: TeachResponse = parse(&s)?;
         teachreply.into()
     }
 
-    /// Checks the image file at `source_path` for similar images based on previously
-    /// taught tags
-    pub fn similar_file(&self, source_path: &str) -> Result<Vec<Tag>> {
-        let url = format!("{}/tagbox/similar", self.url());
-        let s = post_multipart_file(&url, source_path)?;
-        let similar: SimilarResponse = serde_json::from_str(&s)?;
-        similar.into()
-    }
-
-    /// Checks the image at the `image_url` for similar images based on previously
-    /// taught tags.
-    pub fn similar_url(&self, image_url: &str) -> Result<Vec<Tag>> {
-        let url = format!("{}/tagbox/similar", self.url());
-        let params = [("url", image_url)];
-        let s = post_form_vars(&url, &params)?;
-        let similar: SimilarResponse = serde_json::from_str(&s)?;
-        similar.into()
-    }
-
-    /// Checks the image within the base64 encoded string for similar images based on
-    /// previously taught tags.
-    pub fn similar_base64(&self, data: &str) -> Result<Vec<Tag>> {
+    /// Checks the supplied image for similar images based on previously taught tags
+    pub fn similar(&self, image: ImageSource) -> Result<Vec<Tag>> {
         let url = format!("{}/tagbox/similar", self.url());
-        let params = [("base64", data)];
-        let s = post_form_vars(&url, &params)?;
-        let similar: SimilarResponse = serde_json::from_str(&s)?;
+        let s = match image {
+            ImageSource::Url(ref image_url) => {
+                post_form_vars(&self.conn, &url, &[("url", image_url)], Retry::Idempotent)?
+            }
+            image => post_image(&self.conn, &url, image, Retry::Idempotent)?,
+        };
+        let similar: SimilarResponse = parse(&s)?;
         similar.into()
     }
 
@@ -137,23 +107,27 @@ impl Tagbox {
         W: Write,
     {
         let url = format!("{}/tagbox/state", self.url());
-        let mut resp = reqwest::get(&url)?;
-        if resp.status() != StatusCode::Ok {
-            let raw = resp.text()?;
-            Err(Error {
-                kind: Kind::Machinebox(format!("HTTP {}: {}", resp.status(), raw)),
-            })
-        } else {
-            let bytecount = resp.copy_to(buf)?;
-            Ok(bytecount)
-        }
+        let mut resp = get_stream(&self.conn, &url)?;
+        let bytecount = resp.copy_to(buf)?;
+        Ok(bytecount)
     }
 
     /// Submits the state file indicated by the `source_path` parameter to the tagbox
     pub fn post_state(&self, source_path: &str) -> Result<()> {
         let url = format!("{}/tagbox/state", self.url());
-        let raw = post_multipart_file(&url, source_path)?;
-        let state_response:RawBoxResponse = serde_json::from_str(&raw)?;
+        let raw = post_multipart_file(&self.conn, &url, source_path, Retry::Never)?;
+        let state_response: RawBoxResponse = parse(&raw)?;
+        state_response.into()
+    }
+
+    /// Submits the state file read from `reader` to the tagbox. The state is streamed to
+    /// the box as it is read, so it can come straight from memory, a network stream or a
+    /// decompressor without being staged to disk. As the reader can only be consumed once,
+    /// the upload is never retried.
+    pub fn post_state_reader<R: Read + Send + 'static>(&self, reader: R) -> Result<()> {
+        let url = format!("{}/tagbox/state", self.url());
+        let raw = post_multipart_reader_field(&self.conn, &url, "file", reader)?;
+        let state_response: RawBoxResponse = parse(&raw)?;
         state_response.into()
     }
 
@@ -161,16 +135,19 @@ impl Tagbox {
     pub fn post_state_url(&self, state_url: &str) -> Result<()> {
         let url = format!("{}/tagbox/state", self.url());
         let params = [("url", state_url)];
-        let raw = post_form_vars(&url, &params)?;
-        let state_response:RawBoxResponse = serde_json::from_str(&raw)?;
+        let raw = post_form_vars(&self.conn, &url, &params, Retry::Never)?;
+        let state_response: RawBoxResponse = parse(&raw)?;
         state_response.into()
     }
 }
 
 impl BoxClient for Tagbox {
-    fn url(&self) -> &str {
-        &self.url
+    fn connection(&self) -> &Connection {
+        &self.conn
     }
 }
 
 mod types;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,105 @@
+extern crate mockito;
+
+use self::mockito::{mock, Matcher, SERVER_URL};
+use super::Tagbox;
+use std::env;
+use std::fs::{self, File};
+use std::io::{Cursor, Write};
+use std::process;
+use {ImageSource, Kind};
+
+#[test]
+fn teach_path() {
+    let path = env::temp_dir().join(format!("machinebox-tagbox-teach-{}.jpg", process::id()));
+    File::create(&path).unwrap().write_all(b"monkeybytes").unwrap();
+
+    let tb = Tagbox::new(SERVER_URL);
+    let mock = mock("POST", "/tagbox/teach")
+        .match_body(Matcher::Regex(
+            "(?s)name=\"file\".*monkeybytes.*name=\"tag\"\\s*monkeys.*name=\"id\"\\s*monkey.jpg"
+                .to_owned(),
+        ))
+        .with_body(r#"{"success": true}"#)
+        .create();
+    {
+        let res = tb.teach(ImageSource::path(&path), "monkeys", Some("monkey.jpg"));
+        assert!(res.is_ok());
+    }
+    mock.assert();
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn teach_url() {
+    let tb = Tagbox::new(SERVER_URL);
+    let mock = mock("POST", "/tagbox/teach")
+        .match_body(r#"{"tag":"monkeys","id":"monkey.jpg","url":"https://test.machinebox.io/monkey.jpg"}"#)
+        .with_body(r#"{"success": true}"#)
+        .create();
+    {
+        let image = ImageSource::url("https://test.machinebox.io/monkey.jpg");
+        let res = tb.teach(image, "monkeys", Some("monkey.jpg"));
+        assert!(res.is_ok());
+    }
+    mock.assert();
+}
+
+#[test]
+fn similar_url() {
+    let tb = Tagbox::new(SERVER_URL);
+    let mock = mock("POST", "/tagbox/similar")
+        .match_body("url=https%3A%2F%2Ftest.machinebox.io%2Fmonkey.jpg")
+        .with_body(r#"{"success": true, "similar": [{"id": "monkey.jpg", "tag": "monkeys"}]}"#)
+        .create();
+    {
+        let image = ImageSource::url("https://test.machinebox.io/monkey.jpg");
+        let similar = tb.similar(image).unwrap();
+        assert_eq!(similar[0].tag, "monkeys");
+    }
+    mock.assert();
+}
+
+#[test]
+fn teach_base64_without_id() {
+    let tb = Tagbox::new(SERVER_URL);
+    let mock = mock("POST", "/tagbox/teach")
+        .match_body("base64=aW1hZ2U%3D&tag=monkeys")
+        .with_body(r#"{"success": true}"#)
+        .create();
+    {
+        let res = tb.teach(ImageSource::base64("aW1hZ2U="), "monkeys", None);
+        assert!(res.is_ok());
+    }
+    mock.assert();
+}
+
+#[test]
+fn teach_reports_failure() {
+    let tb = Tagbox::new(SERVER_URL);
+    let mock = mock("POST", "/tagbox/teach")
+        .with_body(r#"{"success": false, "error": "tag is required"}"#)
+        .create();
+    {
+        let err = tb
+            .teach(ImageSource::bytes(&b"monkeybytes"[..]), "", None)
+            .unwrap_err();
+        match *err.kind() {
+            Kind::Box(ref msg) => assert_eq!(msg, "tag is required"),
+            ref other => panic!("unexpected error kind: {:?}", other),
+        }
+    }
+    mock.assert();
+}
+
+#[test]
+fn post_state_reader() {
+    let tb = Tagbox::new(SERVER_URL);
+    let upload = mock("POST", "/tagbox/state")
+        .match_body(Matcher::Regex("(?s)name=\"file\".*statebytes".to_owned()))
+        .with_body(r#"{"success": true}"#)
+        .create();
+    {
+        tb.post_state_reader(Cursor::new(b"statebytes".to_vec())).unwrap();
+    }
+    upload.assert();
+}
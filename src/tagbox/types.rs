@@ -1,5 +1,5 @@
 use super::Result;
-use super::{Error, Kind};
+use utils::box_error;
 
 /// A tag represents a single tag that describes an image. Depending on how you
 /// obtained the tag, there might be a confidence score associated with it
@@ -28,21 +28,15 @@ pub struct CheckResponseFull {
     pub custom_tags: Vec<Tag>,
 }
 
-impl Into<Result<CheckResponse>> for CheckResponseFull {
-    fn into(self) -> Result<CheckResponse> {
-        if self.success {
+impl From<CheckResponseFull> for Result<CheckResponse> {
+    fn from(response: CheckResponseFull) -> Result<CheckResponse> {
+        if response.success {
             Ok(CheckResponse {
-                tags: self.tags,
-                custom_tags: self.custom_tags,
+                tags: response.tags,
+                custom_tags: response.custom_tags,
             })
         } else {
-            let s = match self.error {
-                Some(s) => s,
-                None => "Request failed".to_owned(),
-            };
-            Err(Error {
-                kind: Kind::Machinebox(s),
-            })
+            Err(box_error(response.error))
         }
     }
 }
@@ -65,18 +59,12 @@ pub struct TeachResponse {
     pub error: Option<String>,
 }
 
-impl Into<Result<()>> for TeachResponse {
-    fn into(self) -> Result<()> {
-        if self.success {
+impl From<TeachResponse> for Result<()> {
+    fn from(response: TeachResponse) -> Result<()> {
+        if response.success {
             Ok(())
         } else {
-            let s = match self.error {
-                Some(s) => s,
-                None => "Request failed".to_owned(),
-            };
-            Err(Error {
-                kind: Kind::Machinebox(s),
-            })
+            Err(box_error(response.error))
         }
     }
 }
@@ -90,18 +78,12 @@ pub struct SimilarResponse {
     pub similar: Vec<Tag>,
 }
 
-impl Into<Result<Vec<Tag>>> for SimilarResponse {
-    fn into(self) -> Result<Vec<Tag>> {
-        if self.success {
-            Ok(self.similar)
+impl From<SimilarResponse> for Result<Vec<Tag>> {
+    fn from(response: SimilarResponse) -> Result<Vec<Tag>> {
+        if response.success {
+            Ok(response.similar)
         } else {
-            let s = match self.error {
-                Some(s) => s,
-                None => "Request failed".to_owned(),
-            };
-            Err(Error {
-                kind: Kind::Machinebox(s),
-            })
+            Err(box_error(response.error))
         }
     }
 }
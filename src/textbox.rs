@@ -4,24 +4,53 @@
 //! and entity and keyword extraction.
 //!
 //! For more information, see the [textbox docs](https://machinebox.io/docs/textbox)
-use super::BoxClient;
+use super::{BoxClient, Connection};
 use super::Result;
-use reqwest;
-use reqwest::StatusCode;
-use serde_json;
-use Error;
-use Kind;
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::fmt;
+use utils::{parse, post_form_vars, Retry};
 
 /// Textbox represents a client capable of consuming the box's functionality
 pub struct Textbox {
-    url: String,
+    conn: Connection,
 }
 
 /// An analysis contains the results of a call to `check` on the textbox
+///
+/// The `start` and `end` offsets of sentences and entities are byte offsets into the
+/// submitted text. The analysis keeps a copy of that text so they can be resolved with
+/// `sentence_text` and `entity_text`. The text is serialized along with the analysis, so
+/// a stored analysis can still resolve them once it is deserialized again.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Analysis {
     pub sentences: Vec<Sentence>,
     pub keywords: Vec<Keyword>,
+    #[serde(default)]
+    source: String,
+}
+
+impl Analysis {
+    /// The text that was submitted for analysis
+    pub fn text(&self) -> &str {
+        &self.source
+    }
+
+    /// The portion of the submitted text between the `start` and `end` byte offsets. Returns
+    /// `None` if the range falls outside the text or doesn't lie on character boundaries.
+    pub fn slice(&self, start: u32, end: u32) -> Option<&str> {
+        self.source.get(start as usize..end as usize)
+    }
+
+    /// The portion of the submitted text covered by the sentence
+    pub fn sentence_text(&self, sentence: &Sentence) -> Option<&str> {
+        self.slice(sentence.start, sentence.end)
+    }
+
+    /// The portion of the submitted text covered by the entity
+    pub fn entity_text(&self, entity: &Entity) -> Option<&str> {
+        self.slice(entity.start, entity.end)
+    }
 }
 
 /// A sentence identified in an `Analysis`.
@@ -45,12 +74,80 @@ pub struct Sentence {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Entity {
     #[serde(rename = "type")]
-    pub entity_type: String,
+    pub entity_type: EntityType,
     pub text: String,
     pub start: u32,
     pub end: u32,
 }
 
+/// The kind of thing an `Entity` refers to
+///
+/// Types that this client doesn't know about are preserved in `Other`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum EntityType {
+    Person,
+    Place,
+    Date,
+    Time,
+    Money,
+    Number,
+    Url,
+    Email,
+    Other(String),
+}
+
+impl EntityType {
+    /// The name textbox uses for this type of entity
+    pub fn as_str(&self) -> &str {
+        match *self {
+            EntityType::Person => "person",
+            EntityType::Place => "place",
+            EntityType::Date => "date",
+            EntityType::Time => "time",
+            EntityType::Money => "money",
+            EntityType::Number => "number",
+            EntityType::Url => "url",
+            EntityType::Email => "email",
+            EntityType::Other(ref s) => s,
+        }
+    }
+}
+
+impl<'a> From<&'a str> for EntityType {
+    fn from(s: &'a str) -> EntityType {
+        match s {
+            "person" => EntityType::Person,
+            "place" => EntityType::Place,
+            "date" => EntityType::Date,
+            "time" => EntityType::Time,
+            "money" => EntityType::Money,
+            "number" => EntityType::Number,
+            "url" => EntityType::Url,
+            "email" => EntityType::Email,
+            other => EntityType::Other(other.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for EntityType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for EntityType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for EntityType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> ::std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ok(EntityType::from(s.as_str()))
+    }
+}
+
 /// Keywords are simple text tokens identified within sentences
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Keyword {
@@ -64,9 +161,13 @@ impl Textbox {
     ///
     /// * `url` - The URL where the textbox machine is running
     pub fn new(url: &str) -> Textbox {
-        Textbox {
-            url: url.to_owned(),
-        }
+        Textbox::with_connection(Connection::new(url))
+    }
+
+    /// Creates a new textbox client that makes all of its requests through the
+    /// supplied connection
+    pub fn with_connection(conn: Connection) -> Textbox {
+        Textbox { conn }
     }
 
     /// Check performs textual analysis of the input and returns the result in the form of
@@ -74,28 +175,79 @@ impl Textbox {
     pub fn check(&self, text: &str) -> Result<Analysis> {
         let url = format!("{}/textbox/check", self.url());
         let params = [("text", text)];
-        let client = reqwest::Client::new();
-        match client.post(&url).form(&params).send() {
-            Ok(mut response) => {
-                let raw = response.text()?;
-                if response.status() != StatusCode::Ok {
-                    Err(Error {
-                        kind: Kind::Machinebox(format!("HTTP {}: {}", response.status(), raw)),
-                    })
-                } else {
-                    let analysis: Analysis = serde_json::from_str(&raw)?;
-                    Ok(analysis)
-                }
-            }
-            Err(e) => Err(Error {
-                kind: Kind::Reqwest(e),
-            }),
-        }
+        let raw = post_form_vars(&self.conn, &url, &params, Retry::Idempotent)?;
+        let mut analysis: Analysis = parse(&raw)?;
+        analysis.source = text.to_owned();
+        Ok(analysis)
     }
 }
 
 impl BoxClient for Textbox {
-    fn url(&self) -> &str {
-        &self.url
+    fn connection(&self) -> &Connection {
+        &self.conn
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate mockito;
+
+    use self::mockito::{mock, SERVER_URL};
+    use super::{Analysis, EntityType, Textbox};
+    use serde_json;
+
+    #[test]
+    fn entity_types_round_trip() {
+        let types: Vec<EntityType> = serde_json::from_str(r#"["money", "place", "hashtag"]"#).unwrap();
+        assert_eq!(
+            types,
+            vec![EntityType::Money, EntityType::Place, EntityType::Other("hashtag".to_owned())]
+        );
+        assert_eq!(serde_json::to_string(&types).unwrap(), r#"["money","place","hashtag"]"#);
+    }
+
+    #[test]
+    fn offsets_resolve_against_multibyte_text() {
+        let tb = Textbox::new(SERVER_URL);
+        let mock = mock("POST", "/textbox/check")
+            .with_body(
+                r#"{
+    "success": true,
+    "sentences": [
+        {
+            "text": "Pay Zoë €200 tomorrow",
+            "start": 0,
+            "end": 24,
+            "sentiment": 0.5,
+            "entities": [
+                { "type": "person", "text": "Zoë", "start": 4, "end": 8 },
+                { "type": "money", "text": "€200", "start": 9, "end": 15 },
+                { "type": "date", "text": "tomorrow", "start": 10, "end": 24 }
+            ]
+        }
+    ],
+    "keywords": []
+}"#,
+            )
+            .create();
+        {
+            let analysis = tb.check("Pay Zoë €200 tomorrow").unwrap();
+            let sentence = &analysis.sentences[0];
+            assert_eq!(analysis.sentence_text(sentence), Some("Pay Zoë €200 tomorrow"));
+
+            let entities = &sentence.entities;
+            assert_eq!(entities[0].entity_type, EntityType::Person);
+            assert_eq!(analysis.entity_text(&entities[0]), Some("Zoë"));
+            assert_eq!(analysis.entity_text(&entities[1]), Some("€200"));
+            // starts in the middle of the euro sign
+            assert_eq!(analysis.entity_text(&entities[2]), None);
+            assert_eq!(analysis.slice(0, 100), None);
+
+            let stored = serde_json::to_string(&analysis).unwrap();
+            let restored: Analysis = serde_json::from_str(&stored).unwrap();
+            assert_eq!(restored.text(), "Pay Zoë €200 tomorrow");
+            assert_eq!(restored.entity_text(&restored.sentences[0].entities[1]), Some("€200"));
+        }
+        mock.assert();
     }
 }
@@ -1,11 +1,18 @@
-use reqwest;
 
-use super::{Error, Kind, Result};
+use super::{Connection, Error, ImageSource, Kind, Result};
+pub use retry::Retry;
+use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
-use reqwest::StatusCode;
+use serde_json;
+use reqwest::{RequestBuilder, Response, StatusCode, Url};
 use reqwest::multipart::Form;
 use reqwest::multipart::Part;
-use std::io::Read;
+use std::io;
+use std::io::{Cursor, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RawBoxResponse {
@@ -14,172 +21,318 @@ pub struct RawBoxResponse {
     error: Option<String>,
 }
 
-impl Into<Result<()>> for RawBoxResponse {
-   fn into(self) -> Result<()> {
-       if self.success {
-           Ok(())
-       } else {
-           let s = match self.error {
-               Some(s) => s,
-               None => "Request failed".to_owned(),
-           };
-           Err(Error {
-               kind: Kind::Machinebox(s),
-           })
-       }
-   }
+impl From<RawBoxResponse> for Result<()> {
+    fn from(response: RawBoxResponse) -> Result<()> {
+        if response.success {
+            Ok(())
+        } else {
+            Err(box_error(response.error))
+        }
+    }
 }
 
-pub fn post_form_vars<S>(url: &str, vars: S) -> Result<String>
-where
-    S: Serialize,
-{
-    let client = reqwest::Client::new();
-    let url = url.to_owned();
-    match client.post(&url).form(&vars).send() {
-        Ok(mut response) => {
-            let raw = response.text()?;
-            if response.status() != StatusCode::Ok {
-                Err(Error {
-                    kind: Kind::Machinebox(format!("HTTP {}: {}", response.status(), raw)),
-                })
-            } else {
-                Ok(raw)
+/// Creates the error for a response in which the box reported that the request failed
+pub fn box_error(error: Option<String>) -> Error {
+    Error::from(Kind::Box(error.unwrap_or_else(|| "Request failed".to_owned())))
+}
+
+/// Parses the body of a box response, keeping the raw body alongside any parse failure
+pub fn parse<T: DeserializeOwned>(raw: &str) -> Result<T> {
+    serde_json::from_str(raw).map_err(|source| {
+        Error::from(Kind::Deserialization {
+            source,
+            body: raw.to_owned(),
+        })
+    })
+}
+
+/// A response from a box whose body must be read before the connection's overall
+/// timeout elapses
+pub struct BoxResponse {
+    inner: Response,
+    deadline: Option<Instant>,
+}
+
+impl BoxResponse {
+    pub fn status(&self) -> StatusCode {
+        self.inner.status()
+    }
+
+    pub fn text(&mut self) -> Result<String> {
+        let mut raw = String::new();
+        self.read_to_string(&mut raw)?;
+        Ok(raw)
+    }
+
+    pub fn copy_to<W: Write + ?Sized>(&mut self, w: &mut W) -> Result<u64> {
+        let bytecount = io::copy(self, w)?;
+        Ok(bytecount)
+    }
+}
+
+impl Read for BoxResponse {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => {
+                Err(io::Error::new(io::ErrorKind::TimedOut, "timed out"))
             }
+            _ => self.inner.read(buf),
         }
-        Err(e) => Err(Error {
-            kind: Kind::Reqwest(e),
-        }),
     }
 }
 
-pub fn delete_with_response(url: &str) -> Result<String> {
-    let client = reqwest::Client::new();
-    let url = url.to_owned();
-    match client.delete(&url).send() {
-        Ok(mut response) => {
-            let raw = response.text()?;
-            if response.status() != StatusCode::Ok {
-                Err(Error {
-                    kind: Kind::Machinebox(format!("HTTP {}: {}", response.status(), raw)),
-                })
-            } else {
-                Ok(raw)
-            }
+/// Checks that a connection to the box can be established within `timeout`, as the HTTP
+/// client can't bound connecting by itself. Only running out of time is reported here;
+/// any other failure to connect is left for the request itself to report.
+fn check_connect(conn: &Connection, timeout: Duration) -> Result<()> {
+    let addrs = match Url::parse(conn.url()).map(|url| url.to_socket_addrs()) {
+        Ok(Ok(addrs)) => addrs,
+        _ => return Ok(()),
+    };
+    let mut timed_out = false;
+    for addr in addrs {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(_) => return Ok(()),
+            Err(e) => timed_out = e.kind() == io::ErrorKind::TimedOut,
         }
-        Err(e) => Err(Error {
-            kind: Kind::Reqwest(e),
-        }),
+    }
+    if timed_out {
+        Err(Error::from(Kind::Timeout))
+    } else {
+        Ok(())
     }
 }
 
-pub fn patch_json<S>(url: &str, payload: &S) -> Result<String>
+/// Sends a single request, applying the connection's timeouts and credentials. A box
+/// that rejects the credentials is reported as unauthorized.
+fn execute_once(conn: &Connection, mut request: RequestBuilder) -> Result<BoxResponse> {
+    let deadline = conn.timeout().map(|t| Instant::now() + t);
+    if let Some(timeout) = conn.connect_timeout() {
+        check_connect(conn, timeout)?;
+    }
+    conn.authorize(&mut request);
+    let response = request.send()?;
+    if deadline.is_some_and(|d| Instant::now() >= d) {
+        return Err(Error::from(Kind::Timeout));
+    }
+    if response.status() == StatusCode::Unauthorized {
+        return Err(Error::from(Kind::Unauthorized));
+    }
+    Ok(BoxResponse {
+        inner: response,
+        deadline,
+    })
+}
+
+/// Sends the request produced by `build`. Requests that fail for transient reasons are
+/// rebuilt and sent again, as far as the connection's retry policy and the `retry`
+/// safety of the request allow.
+fn execute<F>(conn: &Connection, retry: Retry, mut build: F) -> Result<BoxResponse>
 where
-    S: Serialize,
+    F: FnMut() -> Result<RequestBuilder>,
 {
-    let client = reqwest::Client::new();
-    let url = url.to_owned();
-    match client.patch(&url).json(payload).send() {
-        Ok(mut response) => {
-            let raw = response.text()?;
-            if response.status() != StatusCode::Ok {
-                Err(Error {
-                    kind: Kind::Machinebox(format!("HTTP {}: {}", response.status(), raw)),
-                })
-            } else {
-                Ok(raw)
-            }
+    let policy = conn.retry_policy();
+    let attempts = policy.attempts(retry);
+    let mut attempt = 1;
+    loop {
+        let result = build().and_then(|request| execute_once(conn, request));
+        let transient = match result {
+            Ok(ref response) => policy.retries_status(response.status()),
+            Err(ref e) => policy.retries_error(e),
+        };
+        if !transient || attempt >= attempts {
+            return result;
         }
-        Err(e) => Err(Error {
-            kind: Kind::Reqwest(e),
-        }),
+        thread::sleep(policy.backoff(attempt));
+        attempt += 1;
     }
 }
 
-fn post_multipart(url: &str, form: Form) -> Result<String> {
-    let client = reqwest::Client::new();
-    let url = url.to_owned();
-    match client.post(&url).multipart(form).send() {
-        Ok(mut response) => {
-            let raw = response.text()?;
-            if response.status() != StatusCode::Ok {
-                Err(Error {
-                    kind: Kind::Machinebox(format!("HTTP {}: {}", response.status(), raw)),
-                })
-            } else {
-                Ok(raw)
-            }
-        }
-        Err(e) => Err(Error {
-            kind: Kind::Reqwest(e),
-        }),
+/// Sends the request, returning the response only if the box answered with
+/// an HTTP 200. Any other status is converted into an error carrying the status and body.
+fn send<F>(conn: &Connection, retry: Retry, build: F) -> Result<BoxResponse>
+where
+    F: FnMut() -> Result<RequestBuilder>,
+{
+    let mut response = execute(conn, retry, build)?;
+    if response.status() != StatusCode::Ok {
+        let body = response.text()?;
+        Err(Error::from(Kind::Http {
+            status: response.status().as_u16(),
+            body,
+        }))
+    } else {
+        Ok(response)
     }
 }
 
-pub fn post_multipart_reader<T: Read + Send + 'static>(url: &str, reader: T) -> Result<String> {
-    let part = Part::reader(reader).file_name("file");
-    let form = reqwest::multipart::Form::new().part("file", part);
-    post_multipart(url, form)
+fn send_for_text<F>(conn: &Connection, retry: Retry, build: F) -> Result<String>
+where
+    F: FnMut() -> Result<RequestBuilder>,
+{
+    send(conn, retry, build)?.text()
 }
 
-pub fn post_multipart_reader_parts<T: Read+Send+'static>(url: &str, reader: T, parts: Vec<(&'static str,&'static str)>) -> Result<String> {
-    let rpart = Part::reader(reader).file_name("file");
-    let mut form = reqwest::multipart::Form::new();
-    form = form.part("file", rpart);
-    for (k,v) in parts {
-        form = form.part(k,Part::text(v));
-    }
-    post_multipart(url, form)
-}
-
-pub fn post_multipart_file(url: &str, source_path: &str) -> Result<String> {
-    let form = reqwest::multipart::Form::new().file("file", source_path)?;
-    post_multipart(url, form)
-}
-
-pub fn get_json(url: &str) -> Result<String> {
-    let client = reqwest::Client::new();
-    let url = url.to_owned();
-    match client.get(&url).send() {
-        Ok(mut response) => {
-            let raw = response.text()?;
-            if response.status() == StatusCode::Ok {
-                Ok(raw)
-            } else {
-                Err(Error {
-                    kind: Kind::Machinebox(raw),
-                })
-            }
-        },
-        Err(e) => Err(Error {
-            kind: Kind::Reqwest(e),
-        })
-    }
+pub fn post_form_vars<S>(conn: &Connection, url: &str, vars: &S, retry: Retry) -> Result<String>
+where
+    S: Serialize,
+{
+    send_for_text(conn, retry, || {
+        let mut request = conn.client().post(url);
+        request.form(vars);
+        Ok(request)
+    })
 }
-pub fn post_json<S>(url: &str, payload: &S) -> Result<String>
+
+pub fn delete_with_response(conn: &Connection, url: &str) -> Result<String> {
+    send_for_text(conn, Retry::Idempotent, || Ok(conn.client().delete(url)))
+}
+
+pub fn patch_json<S>(conn: &Connection, url: &str, payload: &S) -> Result<String>
 where
     S: Serialize,
 {
-    let client = reqwest::Client::new();
-    let url = url.to_owned();
-    match client.post(&url).json(payload).send() {
-        Ok(mut response) => {
-            let raw = response.text()?;
-            if response.status() == StatusCode::Ok {
-                Ok(raw)
-            } else {
-                Err(Error {
-                    kind: Kind::Machinebox(raw),
-                })
-            }
-        }
-        Err(e) => Err(Error {
-            kind: Kind::Reqwest(e),
-        }),
+    send_for_text(conn, Retry::Idempotent, || {
+        let mut request = conn.client().patch(url);
+        request.json(payload);
+        Ok(request)
+    })
+}
+
+/// Posts a multipart form. As the form may contain streamed parts that cannot be
+/// replayed, the request is never retried.
+pub fn post_multipart(conn: &Connection, url: &str, form: Form) -> Result<String> {
+    let mut form = Some(form);
+    send_for_text(conn, Retry::Never, || {
+        let mut request = conn.client().post(url);
+        request.multipart(form.take().expect("multipart form is only sent once"));
+        Ok(request)
+    })
+}
+
+/// Posts the contents of `reader` as the multipart file field, along with the supplied
+/// text fields
+pub fn post_multipart_reader_parts<T>(conn: &Connection, url: &str, reader: T,
+                                      fields: &[(&str, &str)]) -> Result<String>
+where
+    T: Read + Send + 'static,
+{
+    let part = Part::reader(reader).file_name("file");
+    let form = with_fields(Form::new().part("file", part), fields);
+    post_multipart(conn, url, form)
+}
+
+/// Posts the contents of `reader` as the named multipart file field
+pub fn post_multipart_reader_field<T>(conn: &Connection, url: &str, field: &str, reader: T)
+                                      -> Result<String>
+where
+    T: Read + Send + 'static,
+{
+    let part = Part::reader(reader).file_name(field.to_owned());
+    post_multipart(conn, url, Form::new().part(field.to_owned(), part))
+}
+
+/// Adds the text fields to a multipart form
+fn with_fields(mut form: Form, fields: &[(&str, &str)]) -> Form {
+    for &(name, value) in fields {
+        form = form.text(name.to_owned(), value.to_owned());
     }
+    form
+}
+
+/// Posts the file at `source_path` as a multipart form field. The file is reopened
+/// for every attempt, so unlike forms built from readers, this request may be retried.
+pub fn post_multipart_file_field<P: AsRef<Path>>(conn: &Connection, url: &str, field: &str,
+                                                 source_path: P, retry: Retry) -> Result<String> {
+    post_multipart_file_field_parts(conn, url, field, source_path.as_ref(), &[], retry)
+}
+
+/// Posts the file at `source_path` as the multipart file field, along with the supplied
+/// text fields. The file is streamed from disk rather than read into memory.
+pub fn post_multipart_file_parts<P: AsRef<Path>>(conn: &Connection, url: &str, source_path: P,
+                                                 fields: &[(&str, &str)], retry: Retry) -> Result<String> {
+    post_multipart_file_field_parts(conn, url, "file", source_path.as_ref(), fields, retry)
+}
+
+fn post_multipart_file_field_parts(conn: &Connection, url: &str, field: &str, source_path: &Path,
+                                   fields: &[(&str, &str)], retry: Retry) -> Result<String> {
+    send_for_text(conn, retry, || {
+        let form = with_fields(Form::new().file(field.to_owned(), source_path)?, fields);
+        let mut request = conn.client().post(url);
+        request.multipart(form);
+        Ok(request)
+    })
+}
+
+pub fn post_multipart_file<P: AsRef<Path>>(conn: &Connection, url: &str, source_path: P,
+                                           retry: Retry) -> Result<String> {
+    post_multipart_file_field(conn, url, "file", source_path, retry)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct URLWrapper {
     pub url: String,
 }
+
+/// Posts an image to the box: files, bytes and readers as a multipart upload, base64 data
+/// as a form value and URLs as a JSON body. Sources that can be replayed are retried as
+/// `retry` allows.
+pub fn post_image(conn: &Connection, url: &str, image: ImageSource, retry: Retry) -> Result<String> {
+    match image {
+        ImageSource::Url(image_url) => post_json(conn, url, &URLWrapper { url: image_url }, retry),
+        image => post_image_parts(conn, url, image, &[], retry),
+    }
+}
+
+/// Posts an image to the box accompanied by the supplied text fields: files, bytes and
+/// readers as a multipart upload, base64 data and URLs as form values
+pub fn post_image_parts(conn: &Connection, url: &str, image: ImageSource, fields: &[(&str, &str)],
+                        retry: Retry) -> Result<String> {
+    match image {
+        ImageSource::Reader(reader) => post_multipart_reader_parts(conn, url, reader, fields),
+        ImageSource::Path(path) => post_multipart_file_parts(conn, url, path, fields, retry),
+        ImageSource::Bytes(bytes) => send_for_text(conn, retry, || {
+            let length = bytes.len() as u64;
+            let part = Part::reader_with_length(Cursor::new(bytes.clone()), length).file_name("file");
+            let form = with_fields(Form::new().part("file", part), fields);
+            let mut request = conn.client().post(url);
+            request.multipart(form);
+            Ok(request)
+        }),
+        ImageSource::Base64(ref data) => post_image_form(conn, url, ("base64", data), fields, retry),
+        ImageSource::Url(ref image_url) => post_image_form(conn, url, ("url", image_url), fields, retry),
+    }
+}
+
+fn post_image_form(conn: &Connection, url: &str, image: (&str, &str), fields: &[(&str, &str)],
+                   retry: Retry) -> Result<String> {
+    let mut vars = vec![image];
+    vars.extend_from_slice(fields);
+    post_form_vars(conn, url, &vars, retry)
+}
+
+pub fn get_json(conn: &Connection, url: &str) -> Result<String> {
+    send_for_text(conn, Retry::Idempotent, || Ok(conn.client().get(url)))
+}
+
+/// Issues a GET request and hands back the successful response so that large bodies,
+/// such as state files, can be streamed rather than read into memory
+pub fn get_stream(conn: &Connection, url: &str) -> Result<BoxResponse> {
+    send(conn, Retry::Idempotent, || Ok(conn.client().get(url)))
+}
+
+/// Issues a GET request and hands back the response regardless of its status code
+pub fn get(conn: &Connection, url: &str) -> Result<BoxResponse> {
+    execute(conn, Retry::Idempotent, || Ok(conn.client().get(url)))
+}
+
+pub fn post_json<S>(conn: &Connection, url: &str, payload: &S, retry: Retry) -> Result<String>
+where
+    S: Serialize,
+{
+    send_for_text(conn, retry, || {
+        let mut request = conn.client().post(url);
+        request.json(payload);
+        Ok(request)
+    })
+}
@@ -1,33 +1,42 @@
 //! A client for utilizing the `videobox` machine
 //!
 //! **Videobox** sends individual frames of videos to other machine boxes for processing.
-//! you must have one of `facebox`, `tagbox`, or `nudebox` running and available to use
-//! videobox.
+//! you must have one of `facebox`, `tagbox`, `nudebox` or `objectbox` running and available
+//! to use videobox.
 //!
 //! For more information, see the [videobox docs](https://machinebox.io/docs/videobox)
-use super::BoxClient;
+use super::{BoxClient, Connection, Error, Kind};
 use super::Result;
-use serde_json;
-use Error;
-use Kind;
+use std::io::Read;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
 
 pub use self::types::{CheckOptions, CheckOptionsBuilder, Video, VideoAnalysis, Range, Item,
-    Nudebox, Facebox, Tagbox, Status};
-use super::utils::{delete_with_response, post_form_vars, get_json};
+    Nudebox, Facebox, Tagbox, Objectbox, TagboxInclude, Status, WaitOptions, WaitOptionsBuilder,
+    MIN_POLL_INTERVAL};
+pub use self::registry::{Job, JobRegistry};
+pub use self::timeline::{Appearance, Source, Timeline};
+use super::utils::{delete_with_response, post_form_vars, post_multipart_file_parts,
+                   post_multipart_reader_parts, get_json, parse, Retry};
 
 use self::types::{VideoResponse, VideoAnalysisResponse};
 
 /// The client for the `videobox` machine box.
 pub struct Videobox {
-    url: String,
+    conn: Connection,
 }
 
 impl Videobox {
     /// Creates a new videobox client
     pub fn new(url: &str) -> Videobox {
-        Videobox {
-            url: url.to_owned()
-        }
+        Videobox::with_connection(Connection::new(url))
+    }
+
+    /// Creates a new videobox client that makes all of its requests through the
+    /// supplied connection
+    pub fn with_connection(conn: Connection) -> Videobox {
+        Videobox { conn }
     }
 
     /// Begins processing the video at the given URL.
@@ -43,15 +52,36 @@ impl Videobox {
             params.push(option.clone());
         }
 
-        let s = post_form_vars(&url, &params)?;
-        let video_result:VideoResponse = serde_json::from_str(&s)?;
+        let s = post_form_vars(&self.conn, &url, &params, Retry::NonIdempotent)?;
+        let video_result: VideoResponse = parse(&s)?;
+        video_result.into()
+    }
+
+    /// Uploads the video file at `source_path` and begins processing it. The file is
+    /// streamed to the box rather than read into memory, so it may be arbitrarily large.
+    pub fn check_path<P: AsRef<Path>>(&self, source_path: P, options: CheckOptions) -> Result<Video> {
+        let url = format!("{}/videobox/check", self.url());
+        let options: Vec<(String, String)> = options.into_iter().collect();
+        let s = post_multipart_file_parts(&self.conn, &url, source_path, &option_fields(&options),
+                                          Retry::NonIdempotent)?;
+        let video_result: VideoResponse = parse(&s)?;
+        video_result.into()
+    }
+
+    /// Uploads the video read from `reader` and begins processing it. The video is
+    /// streamed to the box as it is read, so it is never held in memory in full.
+    pub fn check_reader<T: Read + Send + 'static>(&self, reader: T, options: CheckOptions) -> Result<Video> {
+        let url = format!("{}/videobox/check", self.url());
+        let options: Vec<(String, String)> = options.into_iter().collect();
+        let s = post_multipart_reader_parts(&self.conn, &url, reader, &option_fields(&options))?;
+        let video_result: VideoResponse = parse(&s)?;
         video_result.into()
     }
 
     /// Removes the processing results for a video
     pub fn delete(&self, id: &str) -> Result<()> {
         let url = format!("{}/videobox/results/{}", self.url(), id);
-        let _ = delete_with_response(&url)?;
+        let _ = delete_with_response(&self.conn, &url)?;
 
         Ok(())
     }
@@ -60,26 +90,106 @@ impl Videobox {
     /// This should be called after the video status is completed
     pub fn results(&self, id: &str) -> Result<VideoAnalysis> {
         let url = format!("{}/videobox/results/{}", self.url(), id);
-        let s = get_json(&url)?;
-        let analysis: VideoAnalysisResponse = serde_json::from_str(&s)?;
+        let s = get_json(&self.conn, &url)?;
+        let analysis: VideoAnalysisResponse = parse(&s)?;
         analysis.into()
     }
 
     /// Checks the status of a video processing job
     pub fn status(&self, id: &str) -> Result<Video> {
         let url = format!("{}/videobox/status/{}", self.url(), id);
-        let s = get_json(&url)?;
-        let video: VideoResponse = serde_json::from_str(&s)?;
+        let s = get_json(&self.conn, &url)?;
+        let video: VideoResponse = parse(&s)?;
         video.into()
     }
+
+    /// Blocks until the video with the given ID has been processed, then returns its
+    /// results. The `progress` callback receives the video's status each time it is polled.
+    ///
+    /// Fails with `Kind::VideoFailed` if videobox could not process the video,
+    /// `Kind::VideoExpired` if the video's results are gone (or the ID is unknown), and
+    /// `Kind::VideoTimeout` if the deadline in `options` passes first.
+    pub fn wait<F>(&self, id: &str, options: &WaitOptions, mut progress: F) -> Result<VideoAnalysis>
+    where
+        F: FnMut(&Video),
+    {
+        let started = Instant::now();
+        loop {
+            let video = self.status(id).map_err(|e| expired_if_missing(e, id))?;
+            progress(&video);
+            match next_step(&video, id, options, started)? {
+                Step::Results => return self.results(id).map_err(|e| expired_if_missing(e, id)),
+                Step::Pause(pause) => thread::sleep(pause),
+            }
+        }
+    }
+
+    /// Begins processing the video at the given URL and blocks until its results are
+    /// available, as `check_url` followed by `wait` would
+    pub fn check_and_wait<F>(&self, video_url: &str, check: CheckOptions, options: &WaitOptions,
+                             progress: F) -> Result<VideoAnalysis>
+    where
+        F: FnMut(&Video),
+    {
+        let video = self.check_url(video_url, check)?;
+        self.wait(&video.id, options, progress)
+    }
+}
+
+fn option_fields(options: &[(String, String)]) -> Vec<(&str, &str)> {
+    options.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect()
+}
+
+/// What a wait for a video does after polling its status
+pub(crate) enum Step {
+    /// The video has been processed, so its results can be fetched
+    Results,
+    /// The video is still being processed, so poll again after the pause
+    Pause(Duration),
+}
+
+/// Decides how a wait that began at `started` proceeds, given the latest status of the video
+pub(crate) fn next_step(video: &Video, id: &str, options: &WaitOptions, started: Instant)
+    -> Result<Step> {
+    match video.status {
+        Status::Complete => return Ok(Step::Results),
+        Status::Failed => {
+            return Err(Error::from(Kind::VideoFailed {
+                id: id.to_owned(),
+                error: video.error.clone(),
+            }));
+        }
+        _ => {}
+    }
+
+    match options.deadline {
+        Some(deadline) => match deadline.checked_sub(started.elapsed()) {
+            Some(remaining) if remaining > Duration::from_secs(0) => {
+                Ok(Step::Pause(remaining.min(options.poll_interval)))
+            }
+            _ => Err(Error::from(Kind::VideoTimeout { id: id.to_owned() })),
+        },
+        None => Ok(Step::Pause(options.poll_interval)),
+    }
+}
+
+/// Videobox answers 404 for a video whose results have been garbage collected
+pub(crate) fn expired_if_missing(e: Error, id: &str) -> Error {
+    if e.is_not_found() {
+        Error::from(Kind::VideoExpired { id: id.to_owned() })
+    } else {
+        e
+    }
 }
 
 impl BoxClient for Videobox {
-    fn url(&self) -> &str {
-        &self.url
+    fn connection(&self) -> &Connection {
+        &self.conn
     }
 }
 
+mod registry;
+mod timeline;
 mod types;
 
 #[cfg(test)]
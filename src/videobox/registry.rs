@@ -0,0 +1,209 @@
+use chrono::{DateTime, Utc};
+use serde_json;
+use std::fs::{self, File};
+use std::io::{BufWriter, ErrorKind, Write};
+use std::path::Path;
+
+use super::{Status, Video, VideoAnalysis, Videobox, WaitOptions};
+use utils::parse;
+use {Error, Kind, Result};
+
+/// A video processing job that was submitted to videobox
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Job {
+    /// The ID videobox assigned to the video
+    pub id: String,
+    /// The status of the job when it was last polled
+    pub status: Status,
+    /// When videobox will discard the job's results, if known
+    #[serde(default)]
+    pub expires: Option<DateTime<Utc>>,
+}
+
+impl Job {
+    /// Indicates whether videobox has finished with the job, successfully or not
+    pub fn is_finished(&self) -> bool {
+        matches!(self.status, Status::Complete | Status::Failed)
+    }
+
+    /// Indicates whether the job's results will have been discarded by `now`
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    fn update(&mut self, video: &Video) {
+        self.status = video.status;
+        self.expires = video.expires.or(self.expires);
+    }
+}
+
+/// Keeps track of the videos submitted to videobox, so that they can still be found
+/// after the process that submitted them has gone away. The registry is saved to and
+/// loaded from a JSON file.
+/// # Examples
+/// ```no_run
+/// use machinebox::videobox::{CheckOptionsBuilder, JobRegistry, Videobox, WaitOptions};
+///
+/// let vb = Videobox::new("http://localhost:8080");
+/// let mut jobs = JobRegistry::load("videobox-jobs.json").unwrap();
+///
+/// let opts = CheckOptionsBuilder::new().finish().unwrap();
+/// let video = vb.check_url("https://machinebox.io/samples/videos/bigbuck.mp4", opts).unwrap();
+/// jobs.record(&video);
+/// jobs.save("videobox-jobs.json").unwrap();
+///
+/// // ...after a restart, pick up where we left off
+/// for (id, results) in jobs.resume(&vb, &WaitOptions::default(), |_| {}) {
+///     println!("{}: {:?}", id, results.map(|analysis| analysis.ready));
+/// }
+/// jobs.save("videobox-jobs.json").unwrap();
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct JobRegistry {
+    jobs: Vec<Job>,
+}
+
+impl JobRegistry {
+    /// Creates an empty registry
+    pub fn new() -> JobRegistry {
+        JobRegistry::default()
+    }
+
+    /// Loads a registry previously written by `save`. A registry file that doesn't exist
+    /// yet is treated as an empty registry, and one that can't be parsed fails with
+    /// `Kind::Deserialization`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<JobRegistry> {
+        match fs::read_to_string(path) {
+            Ok(contents) => parse(&contents),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(JobRegistry::new()),
+            Err(e) => Err(Error::from(e)),
+        }
+    }
+
+    /// Writes the registry to the given path as JSON. The file is replaced atomically, so
+    /// a crash part way through never leaves a truncated registry behind.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let mut staging = path.as_os_str().to_owned();
+        staging.push(".tmp");
+        {
+            let mut writer = BufWriter::new(File::create(&staging)?);
+            serde_json::to_writer_pretty(&mut writer, self)?;
+            writer.flush()?;
+        }
+        fs::rename(&staging, path)?;
+        Ok(())
+    }
+
+    /// Records a video returned by `check_url`, `check_path` or `check_reader`, or the
+    /// latest status of one that is already known
+    pub fn record(&mut self, video: &Video) {
+        match self.jobs.iter_mut().find(|job| job.id == video.id) {
+            Some(job) => job.update(video),
+            None => self.jobs.push(Job {
+                id: video.id.clone(),
+                status: video.status,
+                expires: video.expires,
+            }),
+        }
+    }
+
+    /// The job with the given video ID
+    pub fn get(&self, id: &str) -> Option<&Job> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+
+    /// Stops tracking the job with the given video ID, without touching its results
+    pub fn remove(&mut self, id: &str) -> Option<Job> {
+        let index = self.jobs.iter().position(|job| job.id == id)?;
+        Some(self.jobs.remove(index))
+    }
+
+    /// All tracked jobs, in the order they were recorded
+    pub fn jobs(&self) -> &[Job] {
+        &self.jobs
+    }
+
+    /// The tracked jobs that had the given status when they were last polled
+    pub fn with_status(&self, status: Status) -> Vec<&Job> {
+        self.jobs.iter().filter(|job| job.status == status).collect()
+    }
+
+    /// Polls the status of every unfinished job once. Jobs that videobox no longer knows
+    /// about are dropped from the registry.
+    pub fn refresh(&mut self, vb: &Videobox) -> Result<()> {
+        for id in self.unfinished() {
+            match vb.status(&id) {
+                Ok(video) => self.record(&video),
+                Err(ref e) if e.is_not_found() => {
+                    self.remove(&id);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Waits for each unfinished job in turn, as `Videobox::wait` would, and returns the
+    /// outcome for each. Jobs are updated as they are polled, and jobs whose results
+    /// have expired are dropped from the registry.
+    pub fn resume<F>(&mut self, vb: &Videobox, options: &WaitOptions, mut progress: F)
+                     -> Vec<(String, Result<VideoAnalysis>)>
+    where
+        F: FnMut(&Video),
+    {
+        let mut outcomes = Vec::new();
+        for id in self.unfinished() {
+            let mut latest = None;
+            let result = vb.wait(&id, options, |video| {
+                latest = Some(video.clone());
+                progress(video);
+            });
+            if let Some(video) = latest {
+                self.record(&video);
+            }
+            if let Err(ref e) = result {
+                if let Kind::VideoExpired { .. } = *e.kind() {
+                    self.remove(&id);
+                }
+            }
+            outcomes.push((id, result));
+        }
+        outcomes
+    }
+
+    /// Deletes the results of every job that expired by `now` from videobox, and returns
+    /// the outcome for each. A failed delete doesn't stop the others from being attempted.
+    /// Jobs whose results were deleted are dropped from the registry, while jobs whose
+    /// delete failed are kept so that it can be tried again. Results that videobox has
+    /// already discarded are not treated as a failure.
+    pub fn delete_expired(&mut self, vb: &Videobox, now: DateTime<Utc>) -> Vec<(String, Result<()>)> {
+        let expired: Vec<String> = self
+            .jobs
+            .iter()
+            .filter(|job| job.is_expired(now))
+            .map(|job| job.id.clone())
+            .collect();
+
+        let mut outcomes = Vec::new();
+        for id in expired {
+            let result = match vb.delete(&id) {
+                Err(ref e) if e.is_not_found() => Ok(()),
+                result => result,
+            };
+            if result.is_ok() {
+                self.remove(&id);
+            }
+            outcomes.push((id, result));
+        }
+        outcomes
+    }
+
+    fn unfinished(&self) -> Vec<String> {
+        self.jobs
+            .iter()
+            .filter(|job| !job.is_finished())
+            .map(|job| job.id.clone())
+            .collect()
+    }
+}
@@ -1,12 +1,27 @@
 extern crate mockito;
 
-use self::mockito::{mock, SERVER_URL};
-use super::{Videobox, CheckOptionsBuilder};
+use self::mockito::{mock, Matcher, SERVER_URL};
+use std::env;
+use std::fs::{self, File};
+use std::io::{Cursor, Write};
+use std::process;
+use super::{Videobox, CheckOptionsBuilder, Facebox, Item, JobRegistry, Range, Source, Status,
+            Tagbox, TagboxInclude, Timeline, Video, VideoAnalysis, WaitOptionsBuilder,
+            MIN_POLL_INTERVAL};
+use chrono::{self, TimeZone, Utc};
+use serde_json;
+use std::time::Duration;
+use Kind;
 
 #[test]
 fn check_url() {
     let vb = Videobox::new(SERVER_URL);
     let mock = mock("POST", "/videobox/check")
+        .match_body(
+            "url=https%3A%2F%2Ftest.machinebox.io%2Fimage1.png&resultDuration=1h30m0s\
+             &skipframes=2&frameWidth=100&frameHeight=120&faceboxThreshold=0.75\
+             &tagboxInclude=custom&tagboxThreshold=0.7&nudeboxThreshold=0.2",
+        )
         .with_body(r#"{
             "success": true,
             "id": "video-id"
@@ -14,15 +29,16 @@ fn check_url() {
         .create();
     {
         let opts = CheckOptionsBuilder::new()
-            .result_duration("1h0m0s")
-            .skip_frames(2)
-            .skip_seconds(3)
-            .frame_width(100)
-            .frame_height(120)
-            .facebox_threshold(0.75)
-            .tagbox_threshold(0.7)
             .nudebox_threshold(0.2)
-            .finish();
+            .tagbox_threshold(0.7)
+            .tagbox_include(TagboxInclude::Custom)
+            .facebox_threshold(0.75)
+            .frame_height(120)
+            .frame_width(100)
+            .skip_frames(2)
+            .result_duration(Duration::from_secs(5400))
+            .finish()
+            .unwrap();
 
         let res =
             vb.check_url("https://test.machinebox.io/image1.png", opts);
@@ -31,6 +47,89 @@ fn check_url() {
     mock.assert();
 }
 
+#[test]
+fn check_options_reject_conflicting_skips() {
+    let err = CheckOptionsBuilder::new()
+        .skip_frames(2)
+        .skip_seconds(3)
+        .finish()
+        .unwrap_err();
+    match *err.kind() {
+        Kind::InvalidOptions(ref reason) => assert!(reason.contains("skip_seconds")),
+        ref other => panic!("unexpected error kind: {:?}", other),
+    }
+}
+
+#[test]
+fn check_options_reject_out_of_range_thresholds() {
+    for &threshold in &[-0.1, 1.5, f64::NAN] {
+        let res = CheckOptionsBuilder::new().tagbox_threshold(threshold).finish();
+        assert!(res.is_err(), "accepted threshold {}", threshold);
+    }
+    assert!(CheckOptionsBuilder::new().facebox_threshold(1.0).finish().is_ok());
+
+    let opts = CheckOptionsBuilder::new()
+        .result_duration(Duration::from_millis(2500))
+        .finish()
+        .unwrap();
+    assert_eq!(opts.fields(), &[("resultDuration".to_owned(), "0h0m2.5s".to_owned())]);
+}
+
+#[test]
+fn check_options_target_other_boxes() {
+    let opts = CheckOptionsBuilder::new()
+        .box_threshold("logobox", 0.4)
+        .objectbox_threshold(0.6)
+        .box_threshold("carbox", 0.5)
+        .finish()
+        .unwrap();
+    let names: Vec<&str> = opts.fields().iter().map(|f| f.0.as_str()).collect();
+    assert_eq!(names, vec!["objectboxThreshold", "carboxThreshold", "logoboxThreshold"]);
+
+    assert!(CheckOptionsBuilder::new().box_threshold("logobox", 2.0).finish().is_err());
+    assert!(CheckOptionsBuilder::new().box_threshold("", 0.5).finish().is_err());
+    match *CheckOptionsBuilder::new().box_threshold("facebox", 0.5).finish().unwrap_err().kind() {
+        Kind::InvalidOptions(ref reason) => assert!(reason.contains("facebox_threshold")),
+        ref other => panic!("unexpected error kind: {:?}", other),
+    }
+}
+
+#[test]
+fn check_path() {
+    let path = env::temp_dir().join(format!("machinebox-videobox-check-{}.mp4", process::id()));
+    File::create(&path).unwrap().write_all(b"videobytes").unwrap();
+
+    let vb = Videobox::new(SERVER_URL);
+    let mock = mock("POST", "/videobox/check")
+        .match_body(Matcher::Regex(
+            "(?s)name=\"file\".*videobytes.*name=\"skipframes\"\\s*5".to_owned(),
+        ))
+        .with_body(r#"{"success": true, "id": "uploaded-path"}"#)
+        .create();
+    {
+        let opts = CheckOptionsBuilder::new().skip_frames(5).finish().unwrap();
+        let video = vb.check_path(&path, opts).unwrap();
+        assert_eq!(video.id, "uploaded-path");
+    }
+    mock.assert();
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn check_reader() {
+    let vb = Videobox::new(SERVER_URL);
+    let mock = mock("POST", "/videobox/check")
+        .match_body(Matcher::Regex("(?s)name=\"file\".*streamedbytes".to_owned()))
+        .with_body(r#"{"success": true, "id": "uploaded-reader"}"#)
+        .create();
+    {
+        let reader = Cursor::new(b"streamedbytes".to_vec());
+        let video = vb.check_reader(reader, CheckOptionsBuilder::new().finish().unwrap()).unwrap();
+        assert_eq!(video.id, "uploaded-reader");
+    }
+    mock.assert();
+}
+
 #[test]
 fn delete() {
     let vb = Videobox::new(SERVER_URL);
@@ -61,6 +160,45 @@ fn results() {
     mock.assert();
 }
 
+#[test]
+fn results_keep_other_boxes() {
+    let vb = Videobox::new(SERVER_URL);
+    let mock = mock("GET", "/videobox/results/objects")
+        .with_body(r#"{
+            "success": true,
+            "ready": true,
+            "objectbox": {
+                "objects": [
+                    {"key": "car", "instances": [{"start": 0, "end": 12, "start_ms": 0, "end_ms": 480}]}
+                ],
+                "errorsCount": 0
+            },
+            "logobox": {
+                "tags": [
+                    {"key": "acme", "instances": [{"start": 3, "end": 5, "start_ms": 120, "end_ms": 200}]}
+                ],
+                "errorsCount": 1,
+                "lastError": "frame 4: timeout"
+            },
+            "id": "objects",
+            "stats": {"framesCount": 12}
+        }"#)
+        .create();
+    {
+        let analysis = vb.results("objects").unwrap();
+        assert_eq!(analysis.objectbox.as_ref().unwrap().objects[0].key, "car");
+        assert_eq!(analysis.other.keys().collect::<Vec<_>>(), vec!["logobox", "stats"]);
+        // Sections without an error count are kept too
+        assert_eq!(analysis.other["stats"]["framesCount"], 12);
+
+        let logos: Tagbox = analysis.other_results("logobox").unwrap().unwrap();
+        assert_eq!(logos.tags[0].key, "acme");
+        assert_eq!(logos.last_error, Some("frame 4: timeout".to_owned()));
+        assert!(analysis.other_results::<Tagbox>("missing").is_none());
+    }
+    mock.assert();
+}
+
 #[test]
 fn status() {
     let vb = Videobox::new(SERVER_URL);
@@ -78,11 +216,329 @@ fn status() {
         let video = res.unwrap();
         assert_eq!(video.id, "5a50b8067eced76bad103c53dd0f5226");
         assert_eq!(video.status, super::Status::Processing);
+        assert_eq!(video.download_complete_estimate, None);
+        assert_eq!(video.expires, None);
+    }
+    mock.assert();
+}
+
+#[test]
+fn status_parses_times() {
+    let vb = Videobox::new(SERVER_URL);
+    let mock = mock("GET", "/videobox/status/timed")
+        .with_body(r#"{
+            "success": true,
+            "id": "timed",
+            "status": "downloading",
+            "downloadTotal": 2048,
+            "downloadComplete": 1024,
+            "downloadCompleteEstimate": "1m30.5s",
+            "millisecondsComplete": 2500,
+            "lastFrameBase64": "",
+            "expires": "2018-01-07T17:29:42Z"
+        }"#)
+        .create();
+    {
+        let video = vb.status("timed").unwrap();
+        assert_eq!(video.download_complete_estimate, Some(Duration::from_millis(90_500)));
+        assert_eq!(video.duration_complete, Some(Duration::from_millis(2500)));
+        assert_eq!(video.expires, Some(Utc.with_ymd_and_hms(2018, 1, 7, 17, 29, 42).unwrap()));
+        assert_eq!(video.download_total, Some(2048));
+        assert_eq!(video.frames_count, None);
+        assert_eq!(video.last_frame_base64, None);
+
+        let stored = serde_json::to_value(&video).unwrap();
+        assert_eq!(stored["status"], "downloading");
+        assert_eq!(stored["expires"], "2018-01-07T17:29:42Z");
+        let restored: Video = serde_json::from_value(stored).unwrap();
+        assert_eq!(restored, video);
+    }
+    mock.assert();
+}
+
+#[test]
+fn status_decodes_last_frame() {
+    let vb = Videobox::new(SERVER_URL);
+    let mock = mock("GET", "/videobox/status/framed")
+        .with_body(r#"{"success": true, "id": "framed", "status": "processing",
+                       "lastFrameBase64": "/9hmcmFtZQ=="}"#)
+        .create();
+    {
+        let video = vb.status("framed").unwrap();
+        let frame = video.last_frame().unwrap().unwrap();
+        assert_eq!(frame, b"\xff\xd8frame");
+
+        let mut preview = Vec::new();
+        assert!(video.write_last_frame(&mut preview).unwrap());
+        assert_eq!(preview, frame);
+
+        let path = env::temp_dir().join(format!("machinebox-videobox-frame-{}.jpg", process::id()));
+        assert!(video.save_last_frame(&path).unwrap());
+        assert_eq!(fs::read(&path).unwrap(), frame);
+        fs::remove_file(&path).unwrap();
+    }
+    mock.assert();
+}
+
+#[test]
+fn last_frame_reports_malformed_data() {
+    let vb = Videobox::new(SERVER_URL);
+    let garbled = mock("GET", "/videobox/status/garbled")
+        .with_body(r#"{"success": true, "id": "garbled", "status": "processing",
+                       "lastFrameBase64": "not base64!"}"#)
+        .create();
+    {
+        let video = vb.status("garbled").unwrap();
+        let err = video.write_last_frame(Vec::new()).unwrap_err();
+        match *err.kind() {
+            Kind::Base64(_) => {}
+            ref other => panic!("unexpected error kind: {:?}", other),
+        }
+    }
+    garbled.assert();
+
+    let path = env::temp_dir().join(format!("machinebox-videobox-no-frame-{}.jpg", process::id()));
+    let unframed = mock("GET", "/videobox/status/unframed")
+        .with_body(r#"{"success": true, "id": "unframed", "status": "pending"}"#)
+        .create();
+    {
+        let video = vb.status("unframed").unwrap();
+        assert!(video.last_frame().unwrap().is_none());
+        assert!(!video.save_last_frame(&path).unwrap());
+        assert!(!path.exists());
+    }
+    unframed.assert();
+}
+
+#[test]
+fn check_and_wait_reports_progress() {
+    let vb = Videobox::new(SERVER_URL);
+    let check = mock("POST", "/videobox/check")
+        .with_body(r#"{"success": true, "id": "wait-complete"}"#)
+        .create();
+    let status = mock("GET", "/videobox/status/wait-complete")
+        .with_body(r#"{
+            "success": true,
+            "id": "wait-complete",
+            "status": "complete",
+            "framesCount": 720,
+            "framesComplete": 720
+            }"#
+        )
+        .create();
+    let results = mock("GET", "/videobox/results/wait-complete")
+        .with_body(RESULTS_PAYLOAD)
+        .create();
+    {
+        let mut seen = Vec::new();
+        let analysis = vb
+            .check_and_wait(
+                "https://test.machinebox.io/video.mp4",
+                CheckOptionsBuilder::new().finish().unwrap(),
+                &WaitOptionsBuilder::new().finish(),
+                |video| seen.push((video.frames_complete, video.status == Status::Complete)),
+            )
+            .unwrap();
+        assert!(analysis.ready);
+        assert_eq!(seen, vec![(Some(720), true)]);
+    }
+    check.assert();
+    status.assert();
+    results.assert();
+}
+
+#[test]
+fn wait_reports_failure() {
+    let vb = Videobox::new(SERVER_URL);
+    let mock = mock("GET", "/videobox/status/wait-failed")
+        .with_body(r#"{
+            "success": true,
+            "id": "wait-failed",
+            "status": "failed",
+            "error": "unsupported codec"
+            }"#
+        )
+        .create();
+    {
+        let err = vb.wait("wait-failed", &WaitOptionsBuilder::new().finish(), |_| {}).unwrap_err();
+        match *err.kind() {
+            Kind::VideoFailed { ref id, ref error } => {
+                assert_eq!(id, "wait-failed");
+                assert_eq!(error.as_ref().map(String::as_str), Some("unsupported codec"));
+            }
+            ref other => panic!("unexpected error kind: {:?}", other),
+        }
+    }
+    mock.assert();
+}
+
+#[test]
+fn wait_gives_up_at_deadline() {
+    let vb = Videobox::new(SERVER_URL);
+    let _mock = mock("GET", "/videobox/status/wait-slow")
+        .with_body(r#"{"success": true, "id": "wait-slow", "status": "processing"}"#)
+        .create();
+    {
+        let opts = WaitOptionsBuilder::new()
+            .poll_interval(Duration::from_millis(10))
+            .deadline(Duration::from_millis(50))
+            .finish();
+        let mut polls = 0;
+        let err = vb.wait("wait-slow", &opts, |_| polls += 1).unwrap_err();
+        assert!(err.is_timeout());
+        assert!(polls >= 2);
+    }
+}
+
+#[test]
+fn wait_never_polls_without_pausing() {
+    let vb = Videobox::new(SERVER_URL);
+    let _mock = mock("GET", "/videobox/status/wait-eager")
+        .with_body(r#"{"success": true, "id": "wait-eager", "status": "processing"}"#)
+        .create();
+    {
+        let opts = WaitOptionsBuilder::new()
+            .poll_interval(Duration::from_millis(0))
+            .deadline(Duration::from_millis(50))
+            .finish();
+        assert_eq!(opts.poll_interval, MIN_POLL_INTERVAL);
+        let mut polls = 0;
+        assert!(vb.wait("wait-eager", &opts, |_| polls += 1).unwrap_err().is_timeout());
+        assert!(polls <= 6);
+    }
+}
+
+#[test]
+fn wait_reports_expired_results() {
+    let vb = Videobox::new(SERVER_URL);
+    let mock = mock("GET", "/videobox/status/wait-expired")
+        .with_status(404)
+        .create();
+    {
+        let err = vb.wait("wait-expired", &WaitOptionsBuilder::new().finish(), |_| {}).unwrap_err();
+        match *err.kind() {
+            Kind::VideoExpired { ref id } => assert_eq!(id, "wait-expired"),
+            ref other => panic!("unexpected error kind: {:?}", other),
+        }
+    }
+    mock.assert();
+}
+
+#[test]
+fn registry_persists_jobs() {
+    let vb = Videobox::new(SERVER_URL);
+    let mock = mock("POST", "/videobox/check")
+        .with_body(r#"{
+            "success": true,
+            "id": "registry-saved",
+            "status": "pending",
+            "expires": "2018-01-07T17:29:42.611148457+01:00"
+        }"#)
+        .create();
+    let path = env::temp_dir().join(format!("machinebox-videobox-registry-{}.json", process::id()));
+    {
+        let opts = CheckOptionsBuilder::new().finish().unwrap();
+        let video = vb.check_url("https://test.machinebox.io/video.mp4", opts).unwrap();
+        let mut jobs = JobRegistry::new();
+        jobs.record(&video);
+        jobs.save(&path).unwrap();
     }
     mock.assert();
+
+    let jobs = JobRegistry::load(&path).unwrap();
+    let pending = jobs.with_status(Status::Pending);
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].id, "registry-saved");
+    assert_eq!(pending[0].expires, Some(Utc.with_ymd_and_hms(2018, 1, 7, 16, 29, 42).unwrap()
+        + chrono::Duration::nanoseconds(611_148_457)));
+    assert!(jobs.with_status(Status::Complete).is_empty());
+
+    let missing = env::temp_dir()
+        .join(format!("machinebox-no-such-registry-{}.json", process::id()));
+    let missing = JobRegistry::load(missing);
+    assert_eq!(missing.unwrap(), JobRegistry::new());
+
+    fs::write(&path, "{\"jobs\": [").unwrap();
+    match *JobRegistry::load(&path).unwrap_err().kind() {
+        Kind::Deserialization { ref body, .. } => assert_eq!(body, "{\"jobs\": ["),
+        ref other => panic!("unexpected error kind: {:?}", other),
+    }
+    fs::remove_file(&path).unwrap();
 }
 
-const RESULTS_PAYLOAD: &'static str = r#"
+#[test]
+fn registry_resumes_unfinished_jobs() {
+    let vb = Videobox::new(SERVER_URL);
+    let status = mock("GET", "/videobox/status/registry-resume")
+        .with_body(r#"{"success": true, "id": "registry-resume", "status": "complete"}"#)
+        .create();
+    let results = mock("GET", "/videobox/results/registry-resume")
+        .with_body(RESULTS_PAYLOAD)
+        .create();
+    let gone = mock("GET", "/videobox/status/registry-gone")
+        .with_status(404)
+        .create();
+    {
+        let mut jobs: JobRegistry = serde_json::from_str(r#"{"jobs": [
+            {"id": "registry-resume", "status": "processing"},
+            {"id": "registry-gone", "status": "pending"},
+            {"id": "registry-failed", "status": "failed"}
+        ]}"#).unwrap();
+        let outcomes = jobs.resume(&vb, &WaitOptionsBuilder::new().finish(), |_| {});
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes[0].1.as_ref().unwrap().ready);
+        assert!(outcomes[1].1.is_err());
+
+        assert_eq!(jobs.get("registry-resume").unwrap().status, Status::Complete);
+        assert!(jobs.get("registry-gone").is_none());
+        assert_eq!(jobs.jobs().len(), 2);
+    }
+    status.assert();
+    results.assert();
+    gone.assert();
+}
+
+#[test]
+fn registry_deletes_expired_results() {
+    let vb = Videobox::new(SERVER_URL);
+    let expired = mock("DELETE", "/videobox/results/registry-expired")
+        .with_status(200)
+        .create();
+    let collected = mock("DELETE", "/videobox/results/registry-collected")
+        .with_status(404)
+        .create();
+    let broken = mock("DELETE", "/videobox/results/registry-broken")
+        .with_status(500)
+        .create();
+    {
+        let mut jobs: JobRegistry = serde_json::from_str(r#"{"jobs": [
+            {"id": "registry-expired", "status": "complete", "expires": "2018-01-07T17:29:42Z"},
+            {"id": "registry-current", "status": "complete", "expires": "2018-03-01T00:00:00Z"},
+            {"id": "registry-broken", "status": "complete", "expires": "2018-01-01T00:00:00Z"},
+            {"id": "registry-collected", "status": "failed", "expires": "2018-01-01T00:00:00Z"},
+            {"id": "registry-unknown", "status": "pending"}
+        ]}"#).unwrap();
+        let now = Utc.with_ymd_and_hms(2018, 2, 1, 0, 0, 0).unwrap();
+        let outcomes = jobs.delete_expired(&vb, now);
+        let deleted: Vec<(&str, bool)> = outcomes
+            .iter()
+            .map(|(id, result)| (id.as_str(), result.is_ok()))
+            .collect();
+        assert_eq!(
+            deleted,
+            vec![("registry-expired", true), ("registry-broken", false), ("registry-collected", true)]
+        );
+        assert_eq!(outcomes[1].1.as_ref().unwrap_err().status(), Some(500));
+
+        let left: Vec<&str> = jobs.jobs().iter().map(|job| job.id.as_str()).collect();
+        assert_eq!(left, vec!["registry-current", "registry-broken", "registry-unknown"]);
+    }
+    expired.assert();
+    broken.assert();
+    collected.assert();
+}
+
+const RESULTS_PAYLOAD: &str = r#"
  {
 	"success": true,
 	"ready": true,
@@ -204,4 +660,78 @@ const RESULTS_PAYLOAD: &'static str = r#"
 		],
 		"errorsCount": 0
 	}
-}"#;
\ No newline at end of file
+}"#;
+
+fn range(start_ms: isize, end_ms: isize, confidence: f64) -> Range {
+    Range {
+        start: 0,
+        end: 0,
+        start_ms,
+        end_ms,
+        confidence: Some(confidence),
+    }
+}
+
+fn item(key: &str, instances: Vec<Range>) -> Item {
+    Item {
+        key: key.to_owned(),
+        instances,
+    }
+}
+
+fn analysis() -> VideoAnalysis {
+    VideoAnalysis {
+        ready: true,
+        facebox: Some(Facebox {
+            faces: vec![
+                item("John", vec![range(0, 4000, 0.6), range(3000, 6000, 0.9)]),
+                item("Paul", vec![range(5000, 7000, 0.8)]),
+                item("John", vec![range(6000, 8000, 0.7), range(20000, 21000, 0.5)]),
+            ],
+            errors_count: 0,
+            last_error: None,
+        }),
+        tagbox: Some(Tagbox {
+            tags: vec![item("guitar", vec![range(2000, 2500, 0.9)])],
+            error_count: 0,
+            last_error: None,
+        }),
+        nudebox: None,
+        objectbox: None,
+        other: Default::default(),
+    }
+}
+
+#[test]
+fn merges_overlapping_ranges_per_key() {
+    let timeline = Timeline::new(&analysis());
+    let john = timeline.ranges(Source::Facebox, "John");
+    assert_eq!(john.len(), 2);
+    assert_eq!(john[0].start, Duration::from_secs(0));
+    assert_eq!(john[0].end, Duration::from_secs(8));
+    assert_eq!(john[0].confidence, Some(0.9));
+    assert_eq!(john[1].start, Duration::from_secs(20));
+
+    assert_eq!(timeline.on_screen(Source::Facebox, "John"), Duration::from_secs(9));
+    let totals = timeline.screen_time(Source::Facebox);
+    assert_eq!(totals["Paul"], Duration::from_secs(2));
+    assert_eq!(timeline.on_screen(Source::Nudebox, "John"), Duration::from_secs(0));
+}
+
+#[test]
+fn answers_point_and_interval_queries() {
+    let timeline = Timeline::new(&analysis());
+    let keys = |found: Vec<&super::Appearance>| -> Vec<String> {
+        found.iter().map(|a| a.key.clone()).collect()
+    };
+
+    assert_eq!(keys(timeline.at(Duration::from_millis(2200))), vec!["John", "guitar"]);
+    assert_eq!(keys(timeline.at(Duration::from_millis(6500))), vec!["John", "Paul"]);
+    assert!(timeline.at(Duration::from_secs(15)).is_empty());
+
+    assert_eq!(
+        keys(timeline.between(Duration::from_secs(7), Duration::from_secs(20))),
+        vec!["John", "Paul", "John"]
+    );
+    assert!(timeline.between(Duration::from_secs(9), Duration::from_secs(19)).is_empty());
+}
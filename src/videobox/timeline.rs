@@ -0,0 +1,198 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use super::types::{Item, VideoAnalysis};
+
+/// The box whose results an appearance came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Source {
+    Facebox,
+    Tagbox,
+    Nudebox,
+    Objectbox,
+}
+
+/// A continuous period of time during which a face, tag, object or nudity was on
+/// screen. Overlapping and adjoining ranges reported for the same key are merged into
+/// one appearance.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Appearance {
+    /// The box that reported the appearance
+    pub source: Source,
+    /// The face name, tag, object or nudity key
+    pub key: String,
+    /// The offset into the video at which the appearance begins
+    pub start: Duration,
+    /// The offset into the video at which the appearance ends
+    pub end: Duration,
+    /// The highest confidence of the ranges that were merged into this appearance
+    pub confidence: Option<f64>,
+}
+
+impl Appearance {
+    /// The length of time the appearance lasted
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+
+    fn contains(&self, at: Duration) -> bool {
+        self.start <= at && at <= self.end
+    }
+}
+
+/// An index over the results of a video analysis that answers what was on screen at
+/// a given moment or during a given interval.
+/// # Examples
+/// ```no_run
+/// use std::time::Duration;
+/// use machinebox::videobox::{Source, Timeline, Videobox};
+///
+/// let vb = Videobox::new("http://localhost:8080");
+/// let analysis = vb.results("5a50b8067de8f1ca").unwrap();
+/// let timeline = Timeline::new(&analysis);
+/// for appearance in timeline.at(Duration::from_secs(192)) {
+///     println!("{:?} {} was on screen", appearance.source, appearance.key);
+/// }
+/// let screen_time = timeline.on_screen(Source::Facebox, "John Lennon");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Timeline {
+    /// Merged appearances, ordered by start time
+    appearances: Vec<Appearance>,
+    /// The duration of the longest appearance, which bounds how far back a point
+    /// query has to look
+    longest: Duration,
+}
+
+impl Timeline {
+    /// Builds a timeline from the facebox, tagbox, nudebox and objectbox results of an
+    /// analysis
+    pub fn new(analysis: &VideoAnalysis) -> Timeline {
+        let mut appearances = Vec::new();
+        if let Some(ref facebox) = analysis.facebox {
+            merge_items(Source::Facebox, &facebox.faces, &mut appearances);
+        }
+        if let Some(ref tagbox) = analysis.tagbox {
+            merge_items(Source::Tagbox, &tagbox.tags, &mut appearances);
+        }
+        if let Some(ref nudebox) = analysis.nudebox {
+            merge_items(Source::Nudebox, &nudebox.nudity, &mut appearances);
+        }
+        if let Some(ref objectbox) = analysis.objectbox {
+            merge_items(Source::Objectbox, &objectbox.objects, &mut appearances);
+        }
+        appearances.sort_by(|a, b| a.start.cmp(&b.start).then(a.end.cmp(&b.end)));
+
+        let longest = appearances
+            .iter()
+            .map(Appearance::duration)
+            .max()
+            .unwrap_or_default();
+        Timeline { appearances, longest }
+    }
+
+    /// All appearances, ordered by the time at which they begin
+    pub fn appearances(&self) -> &[Appearance] {
+        &self.appearances
+    }
+
+    /// The appearances that were on screen at the given offset into the video
+    pub fn at(&self, at: Duration) -> Vec<&Appearance> {
+        let from = at.checked_sub(self.longest).unwrap_or_default();
+        let first = self.appearances.partition_point(|a| a.start < from);
+        let last = self.appearances.partition_point(|a| a.start <= at);
+        self.appearances[first..last]
+            .iter()
+            .filter(|a| a.contains(at))
+            .collect()
+    }
+
+    /// The appearances that were on screen at any point between `start` and `end`
+    /// inclusive
+    pub fn between(&self, start: Duration, end: Duration) -> Vec<&Appearance> {
+        let last = self.appearances.partition_point(|a| a.start <= end);
+        self.appearances[..last]
+            .iter()
+            .filter(|a| a.end >= start)
+            .collect()
+    }
+
+    /// The merged appearances of one face, tag, object or nudity key, in order
+    pub fn ranges(&self, source: Source, key: &str) -> Vec<&Appearance> {
+        self.appearances
+            .iter()
+            .filter(|a| a.source == source && a.key == key)
+            .collect()
+    }
+
+    /// The total time the given face, tag, object or nudity key was on screen
+    pub fn on_screen(&self, source: Source, key: &str) -> Duration {
+        self.ranges(source, key).iter().map(|a| a.duration()).sum()
+    }
+
+    /// The total time each key reported by `source` was on screen
+    pub fn screen_time(&self, source: Source) -> BTreeMap<String, Duration> {
+        let mut totals = BTreeMap::new();
+        for a in self.appearances.iter().filter(|a| a.source == source) {
+            *totals.entry(a.key.clone()).or_insert_with(Duration::default) += a.duration();
+        }
+        totals
+    }
+}
+
+impl<'a> From<&'a VideoAnalysis> for Timeline {
+    fn from(analysis: &'a VideoAnalysis) -> Timeline {
+        Timeline::new(analysis)
+    }
+}
+
+/// The start, end and confidence of a single range
+type Span = (Duration, Duration, Option<f64>);
+
+/// Merges the overlapping ranges of each item and appends the result to `into`
+fn merge_items(source: Source, items: &[Item], into: &mut Vec<Appearance>) {
+    let mut by_key: BTreeMap<&str, Vec<Span>> = BTreeMap::new();
+    for item in items {
+        let ranges = by_key.entry(item.key.as_str()).or_default();
+        for range in &item.instances {
+            let start = millis(range.start_ms);
+            let end = millis(range.end_ms).max(start);
+            ranges.push((start, end, range.confidence));
+        }
+    }
+
+    for (key, mut ranges) in by_key {
+        ranges.sort_by_key(|span| span.0);
+        let mut current: Option<Appearance> = None;
+        for (start, end, confidence) in ranges {
+            match current {
+                Some(ref mut a) if start <= a.end => {
+                    a.end = a.end.max(end);
+                    a.confidence = max_confidence(a.confidence, confidence);
+                    continue;
+                }
+                _ => {}
+            }
+            into.extend(current.take());
+            current = Some(Appearance {
+                source,
+                key: key.to_owned(),
+                start,
+                end,
+                confidence,
+            });
+        }
+        into.extend(current);
+    }
+}
+
+fn max_confidence(a: Option<f64>, b: Option<f64>) -> Option<f64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn millis(ms: isize) -> Duration {
+    Duration::from_millis(ms.max(0) as u64)
+}
@@ -1,7 +1,16 @@
-use super::Result;
-use super::{Error, Kind};
-use std::collections::HashMap;
+use super::{Error, Kind, Result};
+use utils::box_error;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
 use std::str::FromStr;
+use std::time::Duration;
 use std;
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -31,7 +40,8 @@ pub struct VideoResponse {
 }
 
 /// Indicates the status of a video processing job
-#[derive(Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
 pub enum Status {
     Pending,
     Downloading,
@@ -58,161 +68,458 @@ impl FromStr for Status {
 }
 
 /// Represents a video
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Video {
     pub id: String,
     pub status: Status,
-    pub download_total: i64,
-    pub download_complete: i64,
-    pub download_complete_estimate: String,
-    pub frames_count: isize,
-    pub frames_complete: isize,
-    pub last_frame_base64: String,
-    pub milliseconds_complete: isize,
-    pub expires: String,
-}
-
-impl Into<Result<Video>> for VideoResponse {
-    fn into(self) -> Result<Video> {
-        if self.success {
-            let st = self.status.unwrap_or("unknown".to_owned());
+    /// The size of the video being downloaded, in bytes
+    pub download_total: Option<i64>,
+    /// The number of bytes of the video downloaded so far
+    pub download_complete: Option<i64>,
+    /// How much longer the download is expected to take, if videobox could estimate it
+    pub download_complete_estimate: Option<Duration>,
+    /// The number of frames that will be processed
+    pub frames_count: Option<isize>,
+    /// The number of frames processed so far
+    pub frames_complete: Option<isize>,
+    /// The most recently processed frame, as a base64 encoded JPEG image. Use
+    /// `last_frame` to decode it.
+    pub last_frame_base64: Option<String>,
+    /// How far into the video processing has progressed
+    pub duration_complete: Option<Duration>,
+    /// When videobox will discard the video's results
+    pub expires: Option<DateTime<Utc>>,
+    /// The reason processing failed, if the status is `Failed`
+    pub error: Option<String>,
+}
+
+impl Video {
+    /// Decodes the most recently processed frame into the bytes of a JPEG image. Returns
+    /// `None` if videobox hasn't supplied a frame, and fails with `Kind::Base64` if the
+    /// frame it supplied is malformed.
+    pub fn last_frame(&self) -> Result<Option<Vec<u8>>> {
+        match self.last_frame_base64 {
+            Some(ref frame) => STANDARD
+                .decode(frame)
+                .map(Some)
+                .map_err(|e| Error::from(Kind::Base64(e))),
+            None => Ok(None),
+        }
+    }
+
+    /// Writes the most recently processed frame, as a JPEG image, to `writer`. Returns
+    /// `false`, without writing anything, if videobox hasn't supplied a frame.
+    pub fn write_last_frame<W: Write>(&self, mut writer: W) -> Result<bool> {
+        match self.last_frame()? {
+            Some(frame) => {
+                writer.write_all(&frame)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Saves the most recently processed frame as a JPEG file at `path`. Returns `false`,
+    /// without creating the file, if videobox hasn't supplied a frame.
+    pub fn save_last_frame<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        match self.last_frame()? {
+            Some(frame) => {
+                fs::write(path, frame)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+impl From<VideoResponse> for Result<Video> {
+    fn from(response: VideoResponse) -> Result<Video> {
+        if response.success {
+            let status = response
+                .status
+                .and_then(|st| Status::from_str(&st).ok())
+                .unwrap_or(Status::Unknown);
             Ok(Video {
-                id: self.id,
-                status: Status::from_str(&st).unwrap(),
-                //status: self.status.unwrap_or("unknown".to_owned()),
-                download_total: self.download_total.unwrap_or(0),
-                download_complete: self.download_complete.unwrap_or(0),
-                download_complete_estimate: self.download_complete_estimate.unwrap_or("unknown".to_owned()),
-                frames_count: self.frames_count.unwrap_or(0),
-                frames_complete: self.frames_complete.unwrap_or(0),
-                last_frame_base64: self.last_frame_base64.unwrap_or("".to_owned()),
-                milliseconds_complete: self.milliseconds_complete.unwrap_or(0),
-                expires: self.expires.unwrap_or("".to_owned())
+                id: response.id,
+                status,
+                download_total: response.download_total,
+                download_complete: response.download_complete,
+                download_complete_estimate: response
+                    .download_complete_estimate
+                    .and_then(|estimate| parse_go_duration(&estimate)),
+                frames_count: response.frames_count,
+                frames_complete: response.frames_complete,
+                last_frame_base64: response.last_frame_base64.filter(|frame| !frame.is_empty()),
+                duration_complete: response
+                    .milliseconds_complete
+                    .map(|ms| Duration::from_millis(ms.max(0) as u64)),
+                expires: response.expires.and_then(|expires| parse_timestamp(&expires)),
+                error: response.error,
             })
         } else {
-            let s = match self.error {
-                Some(s) => s,
-                None => "Request failed".to_owned(),
-            };
-            Err(Error {
-                kind: Kind::Machinebox(s),
-            })
+            Err(box_error(response.error))
         }
     }
 }
 
+/// Parses an RFC 3339 timestamp, as produced by Go's `time.Time`
+fn parse_timestamp(timestamp: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+}
+
+/// Parses a duration formatted by Go's `time.Duration`, such as `"1m30.5s"`. Anything
+/// else, including negative durations and videobox's `"unknown"`, yields `None`.
+fn parse_go_duration(s: &str) -> Option<Duration> {
+    match s {
+        "" => return None,
+        "0" => return Some(Duration::from_secs(0)),
+        _ => {}
+    }
+    let mut rest = s;
+    let mut total = Duration::from_secs(0);
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let (number, tail) = rest.split_at(number_len);
+        let unit_len = tail
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+        let nanos_per_unit = match unit {
+            "ns" => 1.0,
+            "us" | "\u{b5}s" | "\u{3bc}s" => 1e3,
+            "ms" => 1e6,
+            "s" => 1e9,
+            "m" => 60e9,
+            "h" => 3600e9,
+            _ => return None,
+        };
+        let value: f64 = number.parse().ok()?;
+        total += Duration::from_nanos((value * nanos_per_unit).round() as u64);
+        rest = tail;
+    }
+    Some(total)
+}
+
+/// Which of tagbox's tags videobox should include in its results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagboxInclude {
+    /// Both the built-in tags and the custom tags taught to tagbox
+    All,
+    /// Only the custom tags taught to tagbox
+    Custom,
+}
+
+impl TagboxInclude {
+    /// The value videobox expects for this setting
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            TagboxInclude::All => "all",
+            TagboxInclude::Custom => "custom",
+        }
+    }
+}
 
 /// Represents the set of options to be passed when invoking `check` to start
-/// video analysis.
+/// video analysis. Options are created with a `CheckOptionsBuilder`, and are always
+/// submitted as form fields in the same order.
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct CheckOptions {
-    kvs: HashMap<String, String>
+    fields: Vec<(String, String)>,
+}
+
+impl CheckOptions {
+    /// The form fields that will be submitted to videobox, in submission order
+    pub fn fields(&self) -> &[(String, String)] {
+        &self.fields
+    }
 }
 
 impl IntoIterator for CheckOptions {
     type Item = (String,String);
-    type IntoIter = ::std::collections::hash_map::IntoIter<String,String>;
+    type IntoIter = ::std::vec::IntoIter<(String,String)>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.kvs.into_iter()
+        self.fields.into_iter()
     }
 }
 
 /// A builder that allows for fluent creation of check options
 /// # Examples
 /// ```
-/// use machinebox::videobox::CheckOptionsBuilder;
-/// 
+/// use std::time::Duration;
+/// use machinebox::videobox::{CheckOptionsBuilder, TagboxInclude};
+///
 /// let opts = CheckOptionsBuilder::new()
-///     .result_duration("1h0m0s")
-///     .skip_frames(2)
+///     .result_duration(Duration::from_secs(3600))
 ///     .skip_seconds(3)
 ///     .frame_width(100)
 ///     .frame_height(120)
 ///     .facebox_threshold(0.75)
+///     .tagbox_include(TagboxInclude::Custom)
 ///     .tagbox_threshold(0.7)
 ///     .nudebox_threshold(0.2)
-///     .finish();
+///     .finish()
+///     .unwrap();
 /// ```
+#[derive(Debug, Clone, Default)]
 pub struct CheckOptionsBuilder {
-    kvs: HashMap<String, String>,
+    result_duration: Option<Duration>,
+    skip_frames: Option<u32>,
+    skip_seconds: Option<u32>,
+    frame_width: Option<u32>,
+    frame_height: Option<u32>,
+    frame_concurrency: Option<u32>,
+    facebox_threshold: Option<f64>,
+    tagbox_include: Option<TagboxInclude>,
+    tagbox_threshold: Option<f64>,
+    nudebox_threshold: Option<f64>,
+    objectbox_threshold: Option<f64>,
+    box_thresholds: BTreeMap<String, f64>,
 }
 
 impl CheckOptionsBuilder {
     /// Creates a new check options builder
     pub fn new() -> Self {
-        CheckOptionsBuilder {
-            kvs: HashMap::new(),
-        }
+        CheckOptionsBuilder::default()
     }
 
     /// Sets the duration the results should be kept in video box before being
     /// garbage collected
-    pub fn result_duration(mut self, duration: &str) -> Self {
-        self.kvs.insert("resultDuration".to_owned(), duration.to_owned());
+    pub fn result_duration(mut self, duration: Duration) -> Self {
+        self.result_duration = Some(duration);
         self
     }
 
-    /// The number of frames to skip between extractions
-    pub fn skip_frames(mut self, frames: isize) -> Self {
-        self.kvs.insert("skipframes".to_owned(), frames.to_string());
+    /// The number of frames to skip between extractions. Cannot be combined with
+    /// `skip_seconds`.
+    pub fn skip_frames(mut self, frames: u32) -> Self {
+        self.skip_frames = Some(frames);
         self
     }
 
-    /// The number of seconds to skip between frame extractions
-    pub fn skip_seconds(mut self, seconds: isize) -> Self {
-        self.kvs.insert("skipseconds".to_owned(), seconds.to_string());
+    /// The number of seconds to skip between frame extractions. Cannot be combined with
+    /// `skip_frames`.
+    pub fn skip_seconds(mut self, seconds: u32) -> Self {
+        self.skip_seconds = Some(seconds);
         self
     }
 
     /// Sets the width of the frame to extract
-    pub fn frame_width(mut self, width: isize) -> Self {
-        self.kvs.insert("frameWidth".to_owned(), width.to_string());
+    pub fn frame_width(mut self, width: u32) -> Self {
+        self.frame_width = Some(width);
         self
     }
 
     /// Sets the height of the frame to extract
-    pub fn frame_height(mut self, height:isize) -> Self {
-        self.kvs.insert("frameHeight".to_owned(), height.to_string());
+    pub fn frame_height(mut self, height: u32) -> Self {
+        self.frame_height = Some(height);
         self
     }
 
     /// Sets the number of frames to process concurrently
-    pub  fn frame_concurrency(mut self, concurrency: isize) -> Self {
-        self.kvs.insert("frameConcurrency".to_owned(), concurrency.to_string());
+    pub fn frame_concurrency(mut self, concurrency: u32) -> Self {
+        self.frame_concurrency = Some(concurrency);
         self
     }
 
-    /// Sets the minimum confidence threshold of facebox matches for the frame
-    /// to be included in the results
+    /// Sets the minimum confidence threshold, between 0 and 1, of facebox matches for
+    /// the frame to be included in the results
     pub fn facebox_threshold(mut self, threshold: f64) -> Self {
-        self.kvs.insert("faceboxThreshold".to_owned(), threshold.to_string());
+        self.facebox_threshold = Some(threshold);
         self
     }
 
-    /// Sets the tagbox include value (e.g. 'all' or 'custom')
-    pub fn tagbox_include(mut self, include: &str) -> Self {
-        self.kvs.insert("tagboxInclude".to_owned(), include.to_owned());
+    /// Sets which of tagbox's tags are included in the results
+    pub fn tagbox_include(mut self, include: TagboxInclude) -> Self {
+        self.tagbox_include = Some(include);
         self
     }
 
-    /// Sets the minimum confidence threshold of tagbox matches for the frame to
-    /// be included in the results
+    /// Sets the minimum confidence threshold, between 0 and 1, of tagbox matches for
+    /// the frame to be included in the results
     pub fn tagbox_threshold(mut self, threshold: f64) -> Self {
-        self.kvs.insert("tagboxThreshold".to_owned(), threshold.to_string());
+        self.tagbox_threshold = Some(threshold);
         self
     }
 
-    /// Sets the minimum confidence threshold of nudebox matches for the frame to
-    /// be included in the results
+    /// Sets the minimum confidence threshold, between 0 and 1, of nudebox matches for
+    /// the frame to be included in the results
     pub fn nudebox_threshold(mut self, threshold: f64) -> Self {
-        self.kvs.insert("nudeboxThreshold".to_owned(), threshold.to_string());
+        self.nudebox_threshold = Some(threshold);
+        self
+    }
+
+    /// Sets the minimum confidence threshold, between 0 and 1, of objectbox detections
+    /// for the frame to be included in the results
+    pub fn objectbox_threshold(mut self, threshold: f64) -> Self {
+        self.objectbox_threshold = Some(threshold);
+        self
+    }
+
+    /// Sets the minimum confidence threshold, between 0 and 1, for a box this builder
+    /// has no dedicated setting for. `name` is the box's name as videobox knows it, such
+    /// as `"mybox"`, which is submitted as the `myboxThreshold` field. The boxes with
+    /// dedicated settings, such as `"facebox"`, are refused by `finish`.
+    pub fn box_threshold(mut self, name: &str, threshold: f64) -> Self {
+        self.box_thresholds.insert(name.to_owned(), threshold);
         self
     }
 
     /// Convert the builder into a set of check options ready for submission to the
-    /// `check` function.
-    pub fn finish(self) -> CheckOptions {
-        CheckOptions {
-            kvs: self.kvs
+    /// `check` function. Fails with `Kind::InvalidOptions` if a threshold lies outside
+    /// 0..=1, if both `skip_frames` and `skip_seconds` were set, or if `box_threshold` was
+    /// given a box that has a dedicated setting.
+    pub fn finish(self) -> Result<CheckOptions> {
+        if self.skip_frames.is_some() && self.skip_seconds.is_some() {
+            return Err(invalid_options(
+                "skip_frames and skip_seconds cannot both be set".to_owned(),
+            ));
+        }
+        check_threshold("facebox_threshold", self.facebox_threshold)?;
+        check_threshold("tagbox_threshold", self.tagbox_threshold)?;
+        check_threshold("nudebox_threshold", self.nudebox_threshold)?;
+        check_threshold("objectbox_threshold", self.objectbox_threshold)?;
+        for (name, &threshold) in &self.box_thresholds {
+            if name.is_empty() {
+                return Err(invalid_options("box_threshold requires a box name".to_owned()));
+            }
+            if DEDICATED_BOXES.contains(&name.as_str()) {
+                return Err(invalid_options(format!(
+                    "the {} threshold must be set with {}_threshold, not box_threshold",
+                    name, name
+                )));
+            }
+            check_threshold(&format!("{} threshold", name), Some(threshold))?;
+        }
+
+        let mut fields = Vec::new();
+        {
+            let mut push = |name: &str, value: Option<String>| {
+                if let Some(value) = value {
+                    fields.push((name.to_owned(), value));
+                }
+            };
+            push("resultDuration", self.result_duration.map(go_duration));
+            push("skipframes", self.skip_frames.map(|v| v.to_string()));
+            push("skipseconds", self.skip_seconds.map(|v| v.to_string()));
+            push("frameWidth", self.frame_width.map(|v| v.to_string()));
+            push("frameHeight", self.frame_height.map(|v| v.to_string()));
+            push("frameConcurrency", self.frame_concurrency.map(|v| v.to_string()));
+            push("faceboxThreshold", self.facebox_threshold.map(|v| v.to_string()));
+            push("tagboxInclude", self.tagbox_include.map(|v| v.as_str().to_owned()));
+            push("tagboxThreshold", self.tagbox_threshold.map(|v| v.to_string()));
+            push("nudeboxThreshold", self.nudebox_threshold.map(|v| v.to_string()));
+            push("objectboxThreshold", self.objectbox_threshold.map(|v| v.to_string()));
+        }
+        for (name, threshold) in self.box_thresholds {
+            fields.push((format!("{}Threshold", name), threshold.to_string()));
+        }
+        Ok(CheckOptions { fields })
+    }
+}
+
+/// The boxes `CheckOptionsBuilder` has dedicated threshold settings for
+const DEDICATED_BOXES: &[&str] = &["facebox", "tagbox", "nudebox", "objectbox"];
+
+fn invalid_options(reason: String) -> Error {
+    Error::from(Kind::InvalidOptions(reason))
+}
+
+fn check_threshold(name: &str, threshold: Option<f64>) -> Result<()> {
+    match threshold {
+        Some(t) if !(0.0..=1.0).contains(&t) => Err(invalid_options(format!(
+            "{} must be between 0 and 1, got {}",
+            name, t
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Formats a duration the way Go's `time.ParseDuration`, which videobox uses, expects it
+fn go_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    let nanos = d.subsec_nanos();
+    let seconds = if nanos == 0 {
+        seconds.to_string()
+    } else {
+        let fraction = format!("{:09}", nanos);
+        format!("{}.{}", seconds, fraction.trim_end_matches('0'))
+    };
+    format!("{}h{}m{}s", hours, minutes, seconds)
+}
+
+/// The shortest amount of time `wait` and `check_and_wait` pause between status checks,
+/// so that a short `poll_interval` can't turn waiting into a busy loop against the box
+pub const MIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Determines how `wait` and `check_and_wait` poll videobox while a video is processed
+#[derive(Debug, Clone)]
+pub struct WaitOptions {
+    pub(crate) poll_interval: Duration,
+    pub(crate) deadline: Option<Duration>,
+}
+
+impl Default for WaitOptions {
+    fn default() -> Self {
+        WaitOptionsBuilder::new().finish()
+    }
+}
+
+/// A builder that allows for fluent creation of wait options
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use machinebox::videobox::WaitOptionsBuilder;
+///
+/// let opts = WaitOptionsBuilder::new()
+///     .poll_interval(Duration::from_millis(500))
+///     .deadline(Duration::from_secs(600))
+///     .finish();
+/// ```
+#[derive(Debug)]
+pub struct WaitOptionsBuilder {
+    poll_interval: Duration,
+    deadline: Option<Duration>,
+}
+
+impl Default for WaitOptionsBuilder {
+    fn default() -> Self {
+        WaitOptionsBuilder::new()
+    }
+}
+
+impl WaitOptionsBuilder {
+    /// Creates a new wait options builder that polls every two seconds, with no deadline
+    pub fn new() -> Self {
+        WaitOptionsBuilder {
+            poll_interval: Duration::from_secs(2),
+            deadline: None,
+        }
+    }
+
+    /// Sets the amount of time to wait between status checks. Intervals shorter than
+    /// `MIN_POLL_INTERVAL` are raised to it.
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval.max(MIN_POLL_INTERVAL);
+        self
+    }
+
+    /// Sets the overall amount of time to wait for the video to be processed before giving up
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Converts the builder into a set of wait options
+    pub fn finish(self) -> WaitOptions {
+        WaitOptions {
+            poll_interval: self.poll_interval,
+            deadline: self.deadline,
         }
     }
 }
@@ -250,30 +557,39 @@ pub struct VideoAnalysisResponse {
     tagbox: Option<Tagbox>,
     #[serde(default)]
     nudebox: Option<Nudebox>,
+    #[serde(default)]
+    objectbox: Option<Objectbox>,
+    #[serde(flatten)]
+    other: BTreeMap<String, Value>,
 }
 
-impl Into<Result<VideoAnalysis>> for VideoAnalysisResponse {
-    fn into(self) -> Result<VideoAnalysis> {
-        if self.success {
+impl From<VideoAnalysisResponse> for Result<VideoAnalysis> {
+    fn from(response: VideoAnalysisResponse) -> Result<VideoAnalysis> {
+        if response.success {
             Ok(VideoAnalysis{
-                ready: self.ready,
-                facebox: self.facebox,
-                tagbox: self.tagbox,
-                nudebox: self.nudebox
+                ready: response.ready,
+                facebox: response.facebox,
+                tagbox: response.tagbox,
+                nudebox: response.nudebox,
+                objectbox: response.objectbox,
+                other: response
+                    .other
+                    .into_iter()
+                    .filter(|(name, _)| !RESPONSE_FIELDS.contains(&name.as_str()))
+                    .collect(),
             })
         } else {
-            let s = match self.error {
-                Some(s) => s,
-                None => "Request failed".to_owned(),
-            };
-            Err(Error {
-                kind: Kind::Machinebox(s),
-            })
+            Err(box_error(response.error))
         }
     }
 }
 
+/// The top-level fields of a results response that describe the video rather than hold
+/// the results of a box, besides those `VideoAnalysisResponse` reads itself
+const RESPONSE_FIELDS: &[&str] = &["id", "status"];
+
 /// The results of a video analysis performed by calling `check`
+#[derive(Debug)]
 pub struct VideoAnalysis {
     /// Indicates whether the results are ready
     pub ready: bool,
@@ -283,6 +599,28 @@ pub struct VideoAnalysis {
     pub tagbox: Option<Tagbox>,
     /// Nudebox analysis results
     pub nudebox: Option<Nudebox>,
+    /// Objectbox analysis results
+    pub objectbox: Option<Objectbox>,
+    /// The results of any other boxes, keyed by box name, exactly as videobox
+    /// reported them. Every top-level section of the response that isn't one of its
+    /// known fields is kept here, whatever it contains.
+    pub other: BTreeMap<String, Value>,
+}
+
+impl VideoAnalysis {
+    /// Interprets the results of a box without a dedicated field, if videobox reported
+    /// any for it. Boxes that report their results in the same shape as tagbox, for
+    /// example, can be read as `analysis.other_results::<Tagbox>("mybox")`.
+    pub fn other_results<T: DeserializeOwned>(&self, name: &str) -> Option<Result<T>> {
+        self.other.get(name).map(|value| {
+            T::deserialize(value).map_err(|source| {
+                Error::from(Kind::Deserialization {
+                    source,
+                    body: value.to_string(),
+                })
+            })
+        })
+    }
 }
 
 /// Facebox-specific results
@@ -313,4 +651,14 @@ pub struct Nudebox {
     pub error_count: isize,
     #[serde(rename = "lastError", default)]
     pub last_error: Option<String>,
-}
\ No newline at end of file
+}
+
+/// Objectbox-specific results
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Objectbox {
+    pub objects: Vec<Item>,
+    #[serde(rename = "errorsCount")]
+    pub error_count: isize,
+    #[serde(rename = "lastError", default)]
+    pub last_error: Option<String>,
+}